@@ -0,0 +1,4 @@
+-- Add migration script here
+-- Issues published before authors were tracked keep a NULL author
+ALTER TABLE newsletter_issues ADD COLUMN user_id uuid NULL REFERENCES users(user_id);
+ALTER TABLE newsletter_issues ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
@@ -0,0 +1,11 @@
+-- Add migration script here
+BEGIN;
+    ALTER TABLE newsletter_issues ADD COLUMN status TEXT NULL;
+    UPDATE newsletter_issues
+        SET status = 'published'
+        WHERE status IS NULL;
+    ALTER TABLE newsletter_issues ALTER COLUMN status SET NOT NULL;
+    ALTER TABLE newsletter_issues ADD COLUMN send_at timestamptz NULL;
+    -- Scheduled issues are only published once the scheduler releases them
+    ALTER TABLE newsletter_issues ALTER COLUMN published_at DROP NOT NULL;
+COMMIT;
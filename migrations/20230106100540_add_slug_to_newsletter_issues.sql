@@ -0,0 +1,11 @@
+-- Add migration script here
+-- e.g. 'Newsletter #1' -> 'newsletter-1-3f2a9c1e', the id prefix keeps slugs unique
+ALTER TABLE newsletter_issues ADD COLUMN slug TEXT NOT NULL
+    GENERATED ALWAYS AS (
+        ltrim(
+            btrim(regexp_replace(lower(title), '[^a-z0-9]+', '-', 'g'), '-')
+                || '-' || left(newsletter_issue_id::text, 8),
+            '-'
+        )
+    ) STORED;
+CREATE UNIQUE INDEX newsletter_issues_slug_idx ON newsletter_issues (slug);
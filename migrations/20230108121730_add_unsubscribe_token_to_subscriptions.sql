@@ -0,0 +1,9 @@
+-- Add migration script here
+BEGIN;
+    ALTER TABLE subscriptions ADD COLUMN unsubscribe_token TEXT NULL;
+    UPDATE subscriptions
+        SET unsubscribe_token = md5(random()::text || id::text)
+        WHERE unsubscribe_token IS NULL;
+    ALTER TABLE subscriptions ALTER COLUMN unsubscribe_token SET NOT NULL;
+    CREATE UNIQUE INDEX subscriptions_unsubscribe_token_idx ON subscriptions (unsubscribe_token);
+COMMIT;
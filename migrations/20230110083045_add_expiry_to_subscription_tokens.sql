@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE subscription_tokens
+    ADD COLUMN created_at timestamptz NOT NULL DEFAULT now(),
+    ADD COLUMN consumed_at timestamptz NULL;
@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE email_change_requests(
+    email_change_token TEXT NOT NULL,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions(id) ON DELETE CASCADE,
+    new_email TEXT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (email_change_token)
+);
@@ -0,0 +1,7 @@
+-- Add migration script here
+-- Only a hash of the address is kept, enough to recognise it without storing it
+CREATE TABLE erased_subscribers(
+    email_hash TEXT NOT NULL,
+    erased_at timestamptz NOT NULL,
+    PRIMARY KEY (email_hash)
+);
@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE subscription_consents(
+    id uuid NOT NULL,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    -- `subscribed` when the form is submitted, `confirmed` when the link is followed
+    event TEXT NOT NULL,
+    source TEXT NOT NULL,
+    consent_text_version TEXT NOT NULL,
+    ip_address TEXT NULL,
+    user_agent TEXT NULL,
+    recorded_at timestamptz NOT NULL,
+    PRIMARY KEY (id)
+);
+CREATE INDEX subscription_consents_subscriber_id_idx ON subscription_consents (subscriber_id);
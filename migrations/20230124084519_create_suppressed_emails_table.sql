@@ -0,0 +1,15 @@
+-- Add migration script here
+-- Addresses that must never be mailed again, whatever their subscription status.
+-- Looked up by a hash of the address: erased subscribers are suppressed without
+-- keeping their email around.
+CREATE TABLE suppressed_emails(
+    email_hash TEXT NOT NULL,
+    email TEXT NULL,
+    -- `bounce`, `complaint`, `manual` or `erased`
+    reason TEXT NOT NULL,
+    suppressed_at timestamptz NOT NULL,
+    PRIMARY KEY (email_hash)
+);
+INSERT INTO suppressed_emails (email_hash, email, reason, suppressed_at)
+SELECT email_hash, NULL, 'erased', erased_at FROM erased_subscribers;
+DROP TABLE erased_subscribers;
@@ -0,0 +1,7 @@
+-- Subscribers waiting for their confirmation email, e.g. after an import
+CREATE TABLE confirmation_email_queue(
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    enqueued_at timestamptz NOT NULL,
+    PRIMARY KEY (subscriber_id)
+);
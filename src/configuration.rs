@@ -1,29 +1,171 @@
-use crate::domain::SubscriberEmail;
+use crate::domain::{SubscriberEmail, ValidationError};
+use crate::email_client::{
+    EmailClient, EmailTransport, FileTransport, PostmarkTransport, SendGridTransport,
+    SmtpTransport, StdoutTransport,
+};
+use crate::issue_delivery_worker::RetryPolicy;
+use crate::preference_links::PreferenceLinks;
 use config::{Config, ConfigError, File};
+use std::path::PathBuf;
+use std::sync::Arc;
 
 #[derive(serde::Deserialize, Clone)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
+    pub issue_delivery: IssueDeliverySettings,
+    pub subscriptions: SubscriptionSettings,
+    pub webhooks: WebhookSettings,
+}
+
+impl Settings {
+    pub fn preference_links(&self) -> PreferenceLinks {
+        PreferenceLinks::new(
+            self.application.base_url.clone(),
+            self.application.hmac_secret.clone(),
+            chrono::Duration::hours(self.subscriptions.preference_link_ttl_hours),
+        )
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailProvider {
+    Postmark,
+    Sendgrid,
+    /// Relay through an SMTP server, configured in `smtp`.
+    Smtp,
+    /// Write `.eml` files into `outbox_directory`, for local development.
+    File,
+    /// Print emails to stdout, for local development.
+    Stdout,
 }
 
 #[derive(serde::Deserialize, Clone)]
 pub struct EmailClientSettings {
+    pub provider: EmailProvider,
     pub base_url: String,
     pub sender_email: String,
     pub authorization_token: String,
     pub timeout: u64,
+    pub smtp: Option<SmtpSettings>,
+    pub outbox_directory: Option<PathBuf>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    /// Authenticate with AUTH PLAIN or LOGIN when both are set.
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTls {
+    /// Upgrade the connection with STARTTLS, usually on port 587.
+    Starttls,
+    /// Connect over TLS straight away, usually on port 465.
+    Implicit,
+    /// No encryption at all, only for relays on a trusted local network.
+    None,
 }
 
 impl EmailClientSettings {
-    pub fn sender(&self) -> Result<SubscriberEmail, String> {
+    pub fn sender(&self) -> Result<SubscriberEmail, ValidationError> {
         SubscriberEmail::parse(self.sender_email.clone())
     }
 
     pub fn timeout(&self) -> std::time::Duration {
         std::time::Duration::from_millis(self.timeout)
     }
+
+    pub fn client(self) -> EmailClient {
+        let sender_email = self.sender().expect("Invalid sender email address");
+        let timeout = self.timeout();
+        let transport: Arc<dyn EmailTransport> = match self.provider {
+            EmailProvider::Postmark => Arc::new(PostmarkTransport::new(
+                self.base_url,
+                self.authorization_token,
+                timeout,
+            )),
+            EmailProvider::Sendgrid => Arc::new(SendGridTransport::new(
+                self.base_url,
+                self.authorization_token,
+                timeout,
+            )),
+            EmailProvider::Smtp => {
+                let smtp = self
+                    .smtp
+                    .expect("The smtp settings are required by the smtp provider");
+                Arc::new(SmtpTransport::new(smtp, timeout).expect("Invalid SMTP settings"))
+            }
+            EmailProvider::File => {
+                let directory = self
+                    .outbox_directory
+                    .expect("The outbox directory is required by the file provider");
+                Arc::new(FileTransport::new(directory).expect("Invalid outbox directory"))
+            }
+            EmailProvider::Stdout => Arc::new(StdoutTransport),
+        };
+        EmailClient::new(sender_email, transport)
+    }
+}
+
+/// 'Basic' credentials expected on webhook calls from the email provider.
+#[derive(serde::Deserialize, Clone)]
+pub struct WebhookSettings {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct IssueDeliverySettings {
+    pub max_attempts: i16,
+    pub retry_base_delay_milliseconds: u64,
+    pub retry_max_delay_milliseconds: u64,
+}
+
+impl IssueDeliverySettings {
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.max_attempts,
+            base_delay: std::time::Duration::from_millis(self.retry_base_delay_milliseconds),
+            max_delay: std::time::Duration::from_millis(self.retry_max_delay_milliseconds),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SubscriptionSettings {
+    pub confirmation_token_ttl_hours: i64,
+    pub expired_token_retention_hours: i64,
+    pub confirmation_reminder_delay_hours: i64,
+    pub unconfirmed_purge_delay_hours: i64,
+    pub preference_link_ttl_hours: i64,
+    /// Recorded with each subscription, when the form does not say which version it showed.
+    pub consent_text_version: String,
+}
+
+impl SubscriptionSettings {
+    pub fn confirmation_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.confirmation_token_ttl_hours)
+    }
+
+    pub fn expired_token_retention(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.expired_token_retention_hours)
+    }
+
+    pub fn confirmation_reminder_delay(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.confirmation_reminder_delay_hours)
+    }
+
+    pub fn unconfirmed_purge_delay(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.unconfirmed_purge_delay_hours)
+    }
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -31,6 +173,7 @@ pub struct ApplicationSettings {
     pub port: u16,
     pub host: String,
     pub base_url: String,
+    pub hmac_secret: String,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -67,9 +210,16 @@ pub fn get_configuration() -> Result<Settings, ConfigError> {
         .try_into()
         .expect("Failed to parse APP_ENVIRONMENT");
 
+    // Secrets are only set in the environment, e.g. `APP_WEBHOOKS__PASSWORD=...`
+    // sets `webhooks.password`
     let base_builder = Config::builder()
         .add_source(File::with_name("config/base"))
-        .add_source(File::with_name(format!("config/{}", env.as_str()).as_str()));
+        .add_source(File::with_name(format!("config/{}", env.as_str()).as_str()))
+        .add_source(
+            config::Environment::with_prefix("APP")
+                .prefix_separator("_")
+                .separator("__"),
+        );
 
     base_builder.build()?.try_deserialize()
 }
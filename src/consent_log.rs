@@ -0,0 +1,123 @@
+use actix_web::http::header::USER_AGENT;
+use actix_web::HttpRequest;
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use sqlx::{PgPool, Postgres, Transaction};
+
+#[derive(Clone, Copy, Debug)]
+pub enum ConsentEvent {
+    Subscribed,
+    Confirmed,
+}
+
+impl ConsentEvent {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ConsentEvent::Subscribed => "subscribed",
+            ConsentEvent::Confirmed => "confirmed",
+        }
+    }
+}
+
+/// How a subscriber gave their consent, kept to prove it later on.
+pub struct ConsentRecord {
+    /// The form, or page, the subscriber used.
+    pub source: String,
+    /// The version of the consent text shown to the subscriber.
+    pub consent_text_version: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ConsentRecord {
+    pub fn from_request(
+        request: &HttpRequest,
+        source: String,
+        consent_text_version: String,
+    ) -> Self {
+        Self {
+            source,
+            consent_text_version,
+            ip_address: request.peer_addr().map(|addr| addr.ip().to_string()),
+            user_agent: request
+                .headers()
+                .get(USER_AGENT)
+                .and_then(|h| h.to_str().ok())
+                .map(ToOwned::to_owned),
+        }
+    }
+}
+
+#[tracing::instrument(name = "Recording subscriber consent", skip(transaction, record))]
+pub async fn record_consent(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    event: ConsentEvent,
+    record: &ConsentRecord,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_consents
+            (id, subscriber_id, event, source, consent_text_version, ip_address, user_agent, recorded_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, now())
+        "#,
+        Uuid::new_v4(),
+        subscriber_id,
+        event.as_str(),
+        record.source,
+        record.consent_text_version,
+        record.ip_address,
+        record.user_agent
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+/// The consent text version shown when the subscriber last signed up, if they used the form.
+pub async fn signed_up_consent_text_version(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    let consent = sqlx::query!(
+        r#"
+        SELECT consent_text_version
+        FROM subscription_consents
+        WHERE subscriber_id = $1 AND event = 'subscribed'
+        ORDER BY recorded_at DESC
+        LIMIT 1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(transaction)
+    .await?;
+    Ok(consent.map(|c| c.consent_text_version))
+}
+
+#[derive(serde::Serialize)]
+pub struct ConsentEntry {
+    event: String,
+    source: String,
+    consent_text_version: String,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+    recorded_at: DateTime<Utc>,
+}
+
+pub async fn get_consent_log(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<ConsentEntry>, sqlx::Error> {
+    sqlx::query_as!(
+        ConsentEntry,
+        r#"
+        SELECT event, source, consent_text_version, ip_address, user_agent, recorded_at
+        FROM subscription_consents
+        WHERE subscriber_id = $1
+        ORDER BY recorded_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -1,7 +1,13 @@
 mod new_subscriber;
+mod newsletter_markdown;
+mod newsletter_template;
 mod subscriber_email;
 mod subscriber_name;
+mod validation_error;
 
 pub use new_subscriber::NewSubscriber;
+pub use newsletter_markdown::{markdown_to_html, markdown_to_text};
+pub use newsletter_template::{MergeValues, NewsletterTemplate};
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use validation_error::ValidationError;
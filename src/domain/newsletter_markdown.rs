@@ -0,0 +1,75 @@
+use pulldown_cmark::{html, Event, Parser, Tag};
+
+/// Render markdown to HTML that is safe to send to subscribers.
+///
+/// Merge fields used as link targets (e.g. `[unsubscribe]({{unsubscribe_url}})`)
+/// come out of the markdown renderer percent-encoded, they are restored so that
+/// the template can still fill them in.
+pub fn markdown_to_html(markdown: &str) -> String {
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new(markdown));
+    ammonia::clean(&unsafe_html)
+        .replace("%7B%7B", "{{")
+        .replace("%7D%7D", "}}")
+}
+
+/// Render markdown to a plain text version of the same content.
+pub fn markdown_to_text(markdown: &str) -> String {
+    let mut text = String::new();
+    let mut link_destinations = vec![];
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(s) | Event::Code(s) => text.push_str(&s),
+            Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            Event::Rule => text.push_str("---\n\n"),
+            Event::Start(Tag::Item) => text.push_str("- "),
+            Event::Start(Tag::Link(_, destination, _)) => link_destinations.push(destination),
+            Event::End(Tag::Link(..)) => {
+                if let Some(destination) = link_destinations.pop() {
+                    text.push_str(&format!(" ({})", destination));
+                }
+            }
+            Event::End(Tag::Item) => text.push('\n'),
+            Event::End(Tag::List(_)) | Event::End(Tag::CodeBlock(_)) => text.push('\n'),
+            Event::End(Tag::Paragraph) | Event::End(Tag::Heading(..)) => text.push_str("\n\n"),
+            _ => {}
+        }
+    }
+    text.trim_end().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{markdown_to_html, markdown_to_text};
+
+    #[test]
+    fn markdown_is_rendered_to_html() {
+        assert_eq!(
+            markdown_to_html("# Hello\n\nSome **bold** text"),
+            "<h1>Hello</h1>\n<p>Some <strong>bold</strong> text</p>\n"
+        );
+    }
+
+    #[test]
+    fn scripts_are_stripped_from_the_html() {
+        let html = markdown_to_html("Hello <script>alert('hi')</script><b onclick=\"x()\">you</b>");
+        assert!(!html.contains("script"));
+        assert!(!html.contains("onclick"));
+        assert!(html.contains("<b>you</b>"));
+    }
+
+    #[test]
+    fn merge_fields_survive_in_link_targets() {
+        let html = markdown_to_html("[Unsubscribe]({{unsubscribe_url}})");
+        assert!(html.contains("href=\"{{unsubscribe_url}}\""));
+    }
+
+    #[test]
+    fn markdown_is_rendered_to_plain_text() {
+        let markdown = "# Hi {{ name }}\n\nRead *this*:\n\n- [one](https://a.com)\n- two\n\nBye";
+        assert_eq!(
+            markdown_to_text(markdown),
+            "Hi {{ name }}\n\nRead this:\n\n- one (https://a.com)\n- two\n\nBye"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use html_escape::encode_quoted_attribute;
+
+/// Newsletter content with `{{ variable }}` placeholders, filled in for every recipient.
+#[derive(Debug)]
+pub struct NewsletterTemplate(Vec<Segment>);
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    Field(MergeField),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeField {
+    Name,
+    Email,
+    UnsubscribeUrl,
+}
+
+impl MergeField {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "name" => Ok(Self::Name),
+            "email" => Ok(Self::Email),
+            "unsubscribe_url" => Ok(Self::UnsubscribeUrl),
+            other => Err(format!(
+                "'{}' is not a known merge field. Use one of 'name', 'email' or 'unsubscribe_url'",
+                other
+            )),
+        }
+    }
+}
+
+/// Values of the merge fields for a single recipient.
+pub struct MergeValues<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+    pub unsubscribe_url: &'a str,
+}
+
+impl MergeValues<'_> {
+    fn get(&self, field: MergeField) -> &str {
+        match field {
+            MergeField::Name => self.name,
+            MergeField::Email => self.email,
+            MergeField::UnsubscribeUrl => self.unsubscribe_url,
+        }
+    }
+}
+
+impl NewsletterTemplate {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut segments = vec![];
+        let mut rest = s;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_owned()));
+            }
+            let after_open = &rest[start + 2..];
+            let end = after_open
+                .find("}}")
+                .ok_or_else(|| "A '{{' placeholder is never closed with '}}'".to_string())?;
+            let field = MergeField::parse(after_open[..end].trim())?;
+            segments.push(Segment::Field(field));
+            rest = &after_open[end + 2..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_owned()));
+        }
+        Ok(Self(segments))
+    }
+
+    /// Values are escaped so that they are safe both in text and in quoted attributes.
+    pub fn render_html(&self, values: &MergeValues) -> String {
+        self.render(|field| encode_quoted_attribute(values.get(field)).into_owned())
+    }
+
+    pub fn render_text(&self, values: &MergeValues) -> String {
+        self.render(|field| values.get(field).to_owned())
+    }
+
+    fn render(&self, value: impl Fn(MergeField) -> String) -> String {
+        self.0
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(s) => s.clone(),
+                Segment::Field(field) => value(*field),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{MergeValues, NewsletterTemplate};
+    use claim::{assert_err, assert_ok};
+
+    fn values() -> MergeValues<'static> {
+        MergeValues {
+            name: "Ursula <Le Guin>",
+            email: "ursula@example.com",
+            unsubscribe_url: "https://example.com/unsubscribe?token=a&b",
+        }
+    }
+
+    #[test]
+    fn content_without_placeholders_is_rendered_as_is() {
+        let template = NewsletterTemplate::parse("<p>Hello there</p>").unwrap();
+        assert_eq!(template.render_html(&values()), "<p>Hello there</p>");
+        assert_eq!(template.render_text(&values()), "<p>Hello there</p>");
+    }
+
+    #[test]
+    fn placeholders_are_replaced_with_and_without_spaces() {
+        let template = NewsletterTemplate::parse("Hi {{ name }}, {{email}}!").unwrap();
+        assert_eq!(
+            template.render_text(&values()),
+            "Hi Ursula <Le Guin>, ursula@example.com!"
+        );
+    }
+
+    #[test]
+    fn values_are_escaped_in_html() {
+        let template = NewsletterTemplate::parse(
+            r#"<p>Hi {{ name }}</p><a href="{{ unsubscribe_url }}">Unsubscribe</a>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            template.render_html(&values()),
+            r#"<p>Hi Ursula &lt;Le Guin&gt;</p><a href="https://example.com/unsubscribe?token=a&amp;b">Unsubscribe</a>"#
+        );
+    }
+
+    #[test]
+    fn unknown_merge_fields_are_rejected() {
+        assert_err!(NewsletterTemplate::parse("Hi {{ surname }}"));
+    }
+
+    #[test]
+    fn unclosed_placeholders_are_rejected() {
+        assert_err!(NewsletterTemplate::parse("Hi {{ name"));
+    }
+
+    #[test]
+    fn single_braces_are_left_alone() {
+        assert_ok!(NewsletterTemplate::parse("a { b } c"));
+    }
+}
@@ -1,14 +1,22 @@
+use crate::domain::ValidationError;
 use validator::validate_email;
 
+/// The longest address SMTP can deliver to.
+const MAX_LENGTH: usize = 254;
+
 #[derive(Debug, Clone)]
 pub struct SubscriberEmail(String);
 
 impl SubscriberEmail {
-    pub fn parse(s: String) -> Result<Self, String> {
-        if validate_email(&s) {
-            Ok(Self(s))
+    pub fn parse(s: String) -> Result<Self, ValidationError> {
+        if s.trim().is_empty() {
+            Err(ValidationError::Empty)
+        } else if s.chars().count() > MAX_LENGTH {
+            Err(ValidationError::TooLong { max: MAX_LENGTH })
+        } else if !validate_email(&s) {
+            Err(ValidationError::InvalidFormat)
         } else {
-            Err(format!("{} is not a valid email", s))
+            Ok(Self(s))
         }
     }
 }
@@ -27,7 +35,7 @@ impl AsRef<String> for SubscriberEmail {
 
 #[cfg(test)]
 mod tests {
-    use crate::domain::SubscriberEmail;
+    use crate::domain::{SubscriberEmail, ValidationError};
     use claim::assert_err;
     use fake::faker::internet::en::SafeEmail;
     use fake::Fake;
@@ -45,13 +53,28 @@ mod tests {
     #[test]
     fn empty_string_is_rejected() {
         let email = "".to_string();
-        assert_err!(SubscriberEmail::parse(email));
+        assert_eq!(
+            SubscriberEmail::parse(email).unwrap_err(),
+            ValidationError::Empty
+        );
+    }
+
+    #[test]
+    fn email_longer_than_254_characters_is_rejected() {
+        let email = format!("{}@domain.com", "a".repeat(250));
+        assert_eq!(
+            SubscriberEmail::parse(email).unwrap_err(),
+            ValidationError::TooLong { max: 254 }
+        );
     }
 
     #[test]
     fn email_with_missing_at_symbol_is_rejected() {
         let email = "helloatdomain.com".to_string();
-        assert_err!(SubscriberEmail::parse(email));
+        assert_eq!(
+            SubscriberEmail::parse(email).unwrap_err(),
+            ValidationError::InvalidFormat
+        );
     }
 
     #[test]
@@ -1,19 +1,28 @@
+use crate::domain::ValidationError;
 use unicode_segmentation::UnicodeSegmentation;
 
+const MAX_LENGTH: usize = 256;
+
 #[derive(Debug)]
 pub struct SubscriberName(String);
 
 impl SubscriberName {
-    pub fn parse(s: String) -> Result<Self, String> {
+    pub fn parse(s: String) -> Result<Self, ValidationError> {
         let is_empty_or_whitespace = s.trim().is_empty();
 
-        let is_too_long = s.graphemes(true).count() > 256;
+        let is_too_long = s.graphemes(true).count() > MAX_LENGTH;
 
         let fordbidden_chars = ['/', '(', ')', '"', '<', '>', '{', '}', '\\'];
         let contains_forbidden_chars = s.chars().any(|g| fordbidden_chars.contains(&g));
 
-        if is_empty_or_whitespace || is_too_long || contains_forbidden_chars {
-            Err(format!("{} is not a valid subscriber name", s))
+        if is_empty_or_whitespace {
+            Err(ValidationError::Empty)
+        } else if is_too_long {
+            Err(ValidationError::TooLong { max: MAX_LENGTH })
+        } else if contains_forbidden_chars {
+            Err(ValidationError::ForbiddenCharacters {
+                forbidden: fordbidden_chars.iter().collect(),
+            })
         } else {
             Ok(Self(s))
         }
@@ -28,7 +37,7 @@ impl AsRef<str> for SubscriberName {
 
 #[cfg(test)]
 mod tests {
-    use crate::domain::SubscriberName;
+    use crate::domain::{SubscriberName, ValidationError};
     use claim::{assert_err, assert_ok};
 
     #[test]
@@ -62,4 +71,20 @@ mod tests {
             assert_err!(SubscriberName::parse(name.to_string()));
         }
     }
+
+    #[test]
+    fn the_reason_for_rejecting_a_name_is_reported() {
+        assert_eq!(
+            SubscriberName::parse(" ".to_string()).unwrap_err(),
+            ValidationError::Empty
+        );
+        assert_eq!(
+            SubscriberName::parse("a".repeat(257)).unwrap_err(),
+            ValidationError::TooLong { max: 256 }
+        );
+        assert!(matches!(
+            SubscriberName::parse("<b>".to_string()).unwrap_err(),
+            ValidationError::ForbiddenCharacters { .. }
+        ));
+    }
 }
@@ -0,0 +1,15 @@
+/// Why a subscriber field was rejected.
+/// Serialised with a `code` tag so that clients can tell the cases apart,
+/// e.g. `{"code": "too_long", "max": 256}`.
+#[derive(thiserror::Error, serde::Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum ValidationError {
+    #[error("must not be empty")]
+    Empty,
+    #[error("must be at most {max} characters long")]
+    TooLong { max: usize },
+    #[error("must not contain any of {forbidden}")]
+    ForbiddenCharacters { forbidden: String },
+    #[error("is not a valid email address")]
+    InvalidFormat,
+}
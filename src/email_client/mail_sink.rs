@@ -0,0 +1,128 @@
+//! Transports for local development, keeping emails on the machine instead of
+//! handing them to a delivery provider.
+use crate::email_client::{Email, EmailTransport};
+use anyhow::Context;
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Writes every email as an `.eml` file, named after a random id, into a directory.
+#[derive(Clone, Debug)]
+pub struct FileTransport {
+    directory: PathBuf,
+    outbox: AsyncFileTransport<Tokio1Executor>,
+}
+
+impl FileTransport {
+    /// Creates `directory` if it does not exist yet.
+    pub fn new(directory: PathBuf) -> Result<Self, anyhow::Error> {
+        std::fs::create_dir_all(&directory).with_context(|| {
+            format!(
+                "Failed to create the outbox directory {}",
+                directory.display()
+            )
+        })?;
+        Ok(Self {
+            outbox: AsyncFileTransport::new(&directory),
+            directory,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for FileTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<(), anyhow::Error> {
+        let id = self.outbox.send(email.to_message()?).await?;
+        let path = self.directory.join(format!("{}.eml", id));
+        tracing::info!(path = %path.display(), "Email written to the outbox");
+        Ok(())
+    }
+}
+
+/// Prints every email to stdout.
+#[derive(Clone, Debug)]
+pub struct StdoutTransport;
+
+#[async_trait::async_trait]
+impl EmailTransport for StdoutTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<(), anyhow::Error> {
+        let message = email.to_message()?.formatted();
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&message)?;
+        stdout.write_all(b"\r\n")?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailClient, EmailHeader, FileTransport};
+    use claim::assert_ok;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn email(address: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(address.to_owned()).unwrap()
+    }
+
+    fn outbox_directory() -> PathBuf {
+        std::env::temp_dir().join(format!("z2p-outbox-{}", Uuid::new_v4()))
+    }
+
+    fn written_emails(directory: &Path) -> Vec<String> {
+        std::fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn file_transport_creates_the_outbox_directory() {
+        let directory = outbox_directory().join("nested");
+
+        assert_ok!(FileTransport::new(directory.clone()));
+
+        assert!(directory.is_dir());
+        std::fs::remove_dir_all(directory.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn file_transport_writes_each_email_to_an_eml_file() {
+        // Arrange
+        let directory = outbox_directory();
+        let transport = FileTransport::new(directory.clone()).unwrap();
+        let email_client = EmailClient::new(email("sender@example.com"), Arc::new(transport));
+        let link = "http://127.0.0.1:8000/subscriptions/confirm?subscription_token=abc123";
+        let headers = [EmailHeader {
+            name: "List-Unsubscribe".into(),
+            value: "<https://example.com/unsubscribe>".into(),
+        }];
+
+        // Act
+        let outcome = email_client
+            .send_mail_with_headers(
+                &email("ursula@example.com"),
+                "Welcome!",
+                &format!("<p>Click <a href=\"{}\">here</a></p>", link),
+                &format!("Visit {}", link),
+                &headers,
+            )
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        let emails = written_emails(&directory);
+        assert_eq!(emails.len(), 1);
+        let eml = &emails[0];
+        assert!(eml.contains("To: ursula@example.com"));
+        assert!(eml.contains("Subject: Welcome!"));
+        assert!(eml.contains("List-Unsubscribe: <https://example.com/unsubscribe>"));
+        assert!(eml.contains("multipart/alternative"));
+        // The link can be copied straight from the file
+        assert!(eml.contains(&format!("Visit {}", link)));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+mod mail_sink;
+mod postmark;
+mod sendgrid;
+mod smtp;
+
+pub use mail_sink::{FileTransport, StdoutTransport};
+pub use postmark::PostmarkTransport;
+pub use sendgrid::SendGridTransport;
+pub use smtp::SmtpTransport;
+
+use crate::domain::SubscriberEmail;
+use anyhow::Context;
+use lettre::message::header::{HeaderName, HeaderValue};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::Message;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Hands emails over to a delivery provider.
+#[async_trait::async_trait]
+pub trait EmailTransport: Debug + Send + Sync {
+    async fn send(&self, email: &Email<'_>) -> Result<(), anyhow::Error>;
+}
+
+/// An email, ready to be handed to a transport.
+pub struct Email<'a> {
+    pub from: &'a SubscriberEmail,
+    pub to: &'a SubscriberEmail,
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub headers: &'a [EmailHeader],
+}
+
+impl Email<'_> {
+    /// The email as a multipart/alternative MIME message.
+    fn to_message(&self) -> Result<Message, anyhow::Error> {
+        let mut builder = Message::builder()
+            .from(Mailbox::new(None, self.from.as_ref().parse()?))
+            .to(Mailbox::new(None, self.to.as_ref().parse()?))
+            .subject(self.subject);
+        for header in self.headers {
+            let name = HeaderName::new_from_ascii(header.name.clone())
+                .with_context(|| format!("Invalid header name `{}`", header.name))?;
+            builder = builder.raw_header(HeaderValue::new(name, header.value.clone()));
+        }
+        let message = builder.multipart(MultiPart::alternative_plain_html(
+            self.text_content.to_owned(),
+            self.html_content.to_owned(),
+        ))?;
+        Ok(message)
+    }
+}
+
+#[derive(serde::Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct EmailHeader {
+    pub name: String,
+    pub value: String,
+}
+
+/// Sends emails from our sender address, whichever transport is configured.
+#[derive(Clone, Debug)]
+pub struct EmailClient {
+    sender: SubscriberEmail,
+    transport: Arc<dyn EmailTransport>,
+}
+
+impl EmailClient {
+    pub fn new(sender: SubscriberEmail, transport: Arc<dyn EmailTransport>) -> Self {
+        Self { sender, transport }
+    }
+
+    pub async fn send_mail(
+        &self,
+        to: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_mail_with_headers(to, subject, html_content, text_content, &[])
+            .await
+    }
+
+    /// Send an email carrying extra headers, e.g. `List-Unsubscribe`.
+    pub async fn send_mail_with_headers(
+        &self,
+        to: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader],
+    ) -> Result<(), anyhow::Error> {
+        let email = Email {
+            from: &self.sender,
+            to,
+            subject,
+            html_content,
+            text_content,
+            headers,
+        };
+        self.transport.send(&email).await
+    }
+}
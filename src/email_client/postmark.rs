@@ -1,44 +1,40 @@
-use crate::domain::SubscriberEmail;
+use crate::email_client::{Email, EmailHeader, EmailTransport};
 use reqwest::Client;
 
+/// Sends emails through Postmark's `/email` API.
 #[derive(Clone, Debug)]
-pub struct EmailClient {
-    sender: SubscriberEmail,
+pub struct PostmarkTransport {
     http_client: Client,
     base_url: String,
     authorization_token: String,
 }
 
-impl EmailClient {
+impl PostmarkTransport {
     pub fn new(
         base_url: String,
-        sender: SubscriberEmail,
         authorization_token: String,
         timeout: std::time::Duration,
     ) -> Self {
         let http_client = Client::builder().timeout(timeout).build().unwrap();
         Self {
-            sender,
             base_url,
             http_client,
             authorization_token,
         }
     }
+}
 
-    pub async fn send_mail(
-        &self,
-        to: &SubscriberEmail,
-        subject: &str,
-        html_content: &str,
-        text_content: &str,
-    ) -> Result<(), reqwest::Error> {
+#[async_trait::async_trait]
+impl EmailTransport for PostmarkTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<(), anyhow::Error> {
         let url = format!("{}/email", self.base_url);
         let request_body = SendEmailRequest {
-            from: self.sender.as_ref(),
-            to: to.as_ref(),
-            subject,
-            html_body: html_content,
-            text_body: text_content,
+            from: email.from.as_ref(),
+            to: email.to.as_ref(),
+            subject: email.subject,
+            html_body: email.html_content,
+            text_body: email.text_content,
+            headers: email.headers,
         };
         self.http_client
             .post(&url)
@@ -59,16 +55,19 @@ struct SendEmailRequest<'a> {
     subject: &'a str,
     html_body: &'a str,
     text_body: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    headers: &'a [EmailHeader],
 }
 
 #[cfg(test)]
 pub mod tests {
     use crate::domain::SubscriberEmail;
-    use crate::email_client::EmailClient;
+    use crate::email_client::{EmailClient, EmailHeader, PostmarkTransport};
     use claim::{assert_err, assert_ok};
     use fake::faker::internet::en::SafeEmail;
     use fake::faker::lorem::en::{Paragraph, Sentence};
     use fake::{Fake, Faker};
+    use std::sync::Arc;
     use wiremock::matchers::{any, header, header_exists, method, path};
     use wiremock::{Mock, MockServer, Request, ResponseTemplate};
 
@@ -85,12 +84,12 @@ pub mod tests {
     }
 
     fn email_client(base_uri: String) -> EmailClient {
-        EmailClient::new(
+        let transport = PostmarkTransport::new(
             base_uri,
-            email(),
             Faker.fake(),
             std::time::Duration::from_millis(200),
-        )
+        );
+        EmailClient::new(email(), Arc::new(transport))
     }
 
     struct SendEmailBodyMatcher;
@@ -135,6 +134,41 @@ pub mod tests {
             .await;
     }
 
+    #[tokio::test]
+    async fn send_email_with_headers_includes_them_in_the_request() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let headers = [EmailHeader {
+            name: "List-Unsubscribe".into(),
+            value: "<https://example.com/unsubscribe>".into(),
+        }];
+
+        // Act
+        let outcome = email_client
+            .send_mail_with_headers(&email(), &subject(), &paragraph(), &paragraph(), &headers)
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(
+            body["Headers"],
+            serde_json::json!([{
+                "Name": "List-Unsubscribe",
+                "Value": "<https://example.com/unsubscribe>"
+            }])
+        );
+    }
+
     #[tokio::test]
     async fn send_email_succeeds_if_response_is_200() {
         // Arrange
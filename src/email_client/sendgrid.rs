@@ -0,0 +1,241 @@
+use crate::email_client::{Email, EmailTransport};
+use reqwest::Client;
+use std::collections::HashMap;
+
+/// Sends emails through SendGrid's v3 `/mail/send` API.
+#[derive(Clone, Debug)]
+pub struct SendGridTransport {
+    http_client: Client,
+    base_url: String,
+    api_key: String,
+}
+
+impl SendGridTransport {
+    pub fn new(base_url: String, api_key: String, timeout: std::time::Duration) -> Self {
+        let http_client = Client::builder().timeout(timeout).build().unwrap();
+        Self {
+            base_url,
+            http_client,
+            api_key,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for SendGridTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<(), anyhow::Error> {
+        let url = format!("{}/v3/mail/send", self.base_url);
+        let request_body = SendEmailRequest {
+            personalizations: [Personalization {
+                to: [Address {
+                    email: email.to.as_ref(),
+                }],
+            }],
+            from: Address {
+                email: email.from.as_ref(),
+            },
+            subject: email.subject,
+            // SendGrid requires the plain text version to come first
+            content: [
+                Content {
+                    content_type: "text/plain",
+                    value: email.text_content,
+                },
+                Content {
+                    content_type: "text/html",
+                    value: email.html_content,
+                },
+            ],
+            headers: email
+                .headers
+                .iter()
+                .map(|h| (h.name.as_str(), h.value.as_str()))
+                .collect(),
+        };
+        self.http_client
+            .post(&url)
+            .bearer_auth(&self.api_key)
+            .json(&request_body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[derive(serde::Serialize)]
+struct SendEmailRequest<'a> {
+    personalizations: [Personalization<'a>; 1],
+    from: Address<'a>,
+    subject: &'a str,
+    content: [Content<'a>; 2],
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    headers: HashMap<&'a str, &'a str>,
+}
+
+#[derive(serde::Serialize)]
+struct Personalization<'a> {
+    to: [Address<'a>; 1],
+}
+
+#[derive(serde::Serialize)]
+struct Address<'a> {
+    email: &'a str,
+}
+
+#[derive(serde::Serialize)]
+struct Content<'a> {
+    #[serde(rename = "type")]
+    content_type: &'a str,
+    value: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailClient, EmailHeader, SendGridTransport};
+    use claim::{assert_err, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::Fake;
+    use std::sync::Arc;
+    use wiremock::matchers::{any, header, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+
+    fn paragraph() -> String {
+        Paragraph(1..5).fake()
+    }
+
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    fn email_client(base_uri: String) -> EmailClient {
+        let transport = SendGridTransport::new(
+            base_uri,
+            "my-api-key".into(),
+            std::time::Duration::from_millis(200),
+        );
+        EmailClient::new(email(), Arc::new(transport))
+    }
+
+    struct SendEmailBodyMatcher;
+
+    impl wiremock::Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                body["personalizations"][0]["to"][0]["email"].is_string()
+                    && body["from"]["email"].is_string()
+                    && body["subject"].is_string()
+                    && body["content"][0]["type"] == "text/plain"
+                    && body["content"][1]["type"] == "text/html"
+            } else {
+                false
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn send_email_sends_the_expected_request() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header("Authorization", "Bearer my-api-key"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/v3/mail/send"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_mail(&email(), &subject(), &paragraph(), &paragraph())
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_with_headers_includes_them_in_the_request() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let headers = [EmailHeader {
+            name: "List-Unsubscribe".into(),
+            value: "<https://example.com/unsubscribe>".into(),
+        }];
+
+        // Act
+        let outcome = email_client
+            .send_mail_with_headers(&email(), &subject(), &paragraph(), &paragraph(), &headers)
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(
+            body["headers"],
+            serde_json::json!({"List-Unsubscribe": "<https://example.com/unsubscribe>"})
+        );
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_response_is_500() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_mail(&email(), &subject(), &paragraph(), &paragraph())
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_server_takes_too_long() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        let response = ResponseTemplate::new(202).set_delay(std::time::Duration::from_secs(180));
+        Mock::given(any())
+            .respond_with(response)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_mail(&email(), &subject(), &paragraph(), &paragraph())
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+}
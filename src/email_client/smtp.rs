@@ -0,0 +1,294 @@
+use crate::configuration::{SmtpSettings, SmtpTls};
+use crate::email_client::{Email, EmailTransport};
+use anyhow::Context;
+use lettre::transport::smtp::authentication::{Credentials, Mechanism};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+
+/// Relays emails through an SMTP server, as multipart/alternative messages.
+#[derive(Clone, Debug)]
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+    timeout: std::time::Duration,
+}
+
+impl SmtpTransport {
+    pub fn new(
+        settings: SmtpSettings,
+        timeout: std::time::Duration,
+    ) -> Result<Self, anyhow::Error> {
+        let builder = match settings.tls {
+            SmtpTls::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)?
+            }
+            SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host)?,
+            SmtpTls::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host)
+            }
+        };
+        let mut builder = builder.port(settings.port).timeout(Some(timeout));
+        if let (Some(username), Some(password)) = (settings.username, settings.password) {
+            builder = builder
+                .credentials(Credentials::new(username, password))
+                .authentication(vec![Mechanism::Plain, Mechanism::Login]);
+        }
+        Ok(Self {
+            mailer: builder.build(),
+            timeout,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<(), anyhow::Error> {
+        let message = email.to_message()?;
+        // lettre only applies its timeout to connecting, not to the SMTP conversation
+        tokio::time::timeout(self.timeout, self.mailer.send(message))
+            .await
+            .context("Timed out while talking to the SMTP server")??;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration::{SmtpSettings, SmtpTls};
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailClient, EmailHeader, SmtpTransport};
+    use claim::{assert_err, assert_ok};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// What the stand-in server was told during one SMTP session.
+    #[derive(Default, Debug)]
+    struct Session {
+        credentials: Option<(String, String)>,
+        mail_from: String,
+        rcpt_to: String,
+        data: String,
+    }
+
+    /// A minimal SMTP server, speaking just enough of the protocol to accept a
+    /// message, with the given AUTH mechanisms. Recipients starting with
+    /// `reject` are refused.
+    async fn spawn_smtp_server(auth_mechanisms: &'static str) -> (u16, Arc<Mutex<Vec<Session>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sessions = Arc::new(Mutex::new(vec![]));
+        let recorded = sessions.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let session = serve_session(stream, auth_mechanisms).await;
+                recorded.lock().unwrap().push(session);
+            }
+        });
+        (port, sessions)
+    }
+
+    async fn serve_session(stream: TcpStream, auth_mechanisms: &str) -> Session {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let mut session = Session::default();
+        writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let reply = if line.starts_with("EHLO") {
+                format!("250-localhost\r\n250 AUTH {}\r\n", auth_mechanisms)
+            } else if let Some(response) = line.strip_prefix("AUTH PLAIN ") {
+                // authzid \0 authcid \0 password
+                let decoded = decode(response);
+                let mut parts = decoded.split('\0').skip(1).map(ToOwned::to_owned);
+                session.credentials = Some((parts.next().unwrap(), parts.next().unwrap()));
+                "235 Authenticated\r\n".into()
+            } else if line == "AUTH LOGIN" {
+                writer.write_all(b"334 VXNlcm5hbWU6\r\n").await.unwrap();
+                let username = decode(&lines.next_line().await.unwrap().unwrap());
+                writer.write_all(b"334 UGFzc3dvcmQ6\r\n").await.unwrap();
+                let password = decode(&lines.next_line().await.unwrap().unwrap());
+                session.credentials = Some((username, password));
+                "235 Authenticated\r\n".into()
+            } else if let Some(from) = line.strip_prefix("MAIL FROM:") {
+                session.mail_from = from.to_owned();
+                "250 OK\r\n".into()
+            } else if let Some(to) = line.strip_prefix("RCPT TO:") {
+                session.rcpt_to = to.to_owned();
+                if to.starts_with("<reject") {
+                    "550 No such user\r\n".into()
+                } else {
+                    "250 OK\r\n".into()
+                }
+            } else if line == "DATA" {
+                writer.write_all(b"354 Go ahead\r\n").await.unwrap();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line == "." {
+                        break;
+                    }
+                    session.data.push_str(&line);
+                    session.data.push('\n');
+                }
+                "250 Queued\r\n".into()
+            } else if line == "QUIT" {
+                writer.write_all(b"221 Bye\r\n").await.unwrap();
+                break;
+            } else {
+                "250 OK\r\n".into()
+            };
+            writer.write_all(reply.as_bytes()).await.unwrap();
+        }
+        session
+    }
+
+    fn decode(line: &str) -> String {
+        String::from_utf8(base64::decode(line).unwrap()).unwrap()
+    }
+
+    fn email_client(port: u16, credentials: Option<(&str, &str)>) -> EmailClient {
+        let settings = SmtpSettings {
+            host: "127.0.0.1".into(),
+            port,
+            tls: SmtpTls::None,
+            username: credentials.map(|c| c.0.to_owned()),
+            password: credentials.map(|c| c.1.to_owned()),
+        };
+        let transport =
+            SmtpTransport::new(settings, std::time::Duration::from_millis(500)).unwrap();
+        EmailClient::new(email("sender@example.com"), Arc::new(transport))
+    }
+
+    fn email(address: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(address.to_owned()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn send_email_relays_a_multipart_alternative_message() {
+        // Arrange
+        let (port, sessions) = spawn_smtp_server("PLAIN LOGIN").await;
+        let email_client = email_client(port, None);
+
+        // Act
+        let outcome = email_client
+            .send_mail(
+                &email("ursula@example.com"),
+                "Newsletter #1",
+                "<p>Hello</p>",
+                "Hello",
+            )
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        let sessions = sessions.lock().unwrap();
+        let session = &sessions[0];
+        assert!(session.credentials.is_none());
+        assert_eq!(session.mail_from, "<sender@example.com>");
+        assert_eq!(session.rcpt_to, "<ursula@example.com>");
+        assert!(session.data.contains("Subject: Newsletter #1"));
+        assert!(session.data.contains("multipart/alternative"));
+        let text = session.data.find("Content-Type: text/plain").unwrap();
+        let html = session.data.find("Content-Type: text/html").unwrap();
+        assert!(text < html);
+        assert!(session.data.contains("<p>Hello</p>"));
+    }
+
+    #[tokio::test]
+    async fn send_email_with_headers_includes_them_in_the_message() {
+        // Arrange
+        let (port, sessions) = spawn_smtp_server("PLAIN").await;
+        let email_client = email_client(port, None);
+        let headers = [EmailHeader {
+            name: "List-Unsubscribe".into(),
+            value: "<https://example.com/unsubscribe>".into(),
+        }];
+
+        // Act
+        let outcome = email_client
+            .send_mail_with_headers(
+                &email("ursula@example.com"),
+                "Newsletter #1",
+                "<p>Hello</p>",
+                "Hello",
+                &headers,
+            )
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        assert!(sessions.lock().unwrap()[0]
+            .data
+            .contains("List-Unsubscribe: <https://example.com/unsubscribe>"));
+    }
+
+    #[tokio::test]
+    async fn send_email_authenticates_with_auth_plain() {
+        // Arrange
+        let (port, sessions) = spawn_smtp_server("PLAIN").await;
+        let email_client = email_client(port, Some(("user", "secret")));
+
+        // Act
+        let outcome = email_client
+            .send_mail(&email("ursula@example.com"), "Hi", "<p>Hi</p>", "Hi")
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        assert_eq!(
+            sessions.lock().unwrap()[0].credentials,
+            Some(("user".into(), "secret".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn send_email_authenticates_with_auth_login() {
+        // Arrange
+        let (port, sessions) = spawn_smtp_server("LOGIN").await;
+        let email_client = email_client(port, Some(("user", "secret")));
+
+        // Act
+        let outcome = email_client
+            .send_mail(&email("ursula@example.com"), "Hi", "<p>Hi</p>", "Hi")
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        assert_eq!(
+            sessions.lock().unwrap()[0].credentials,
+            Some(("user".into(), "secret".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_recipient_is_refused() {
+        // Arrange
+        let (port, _) = spawn_smtp_server("PLAIN").await;
+        let email_client = email_client(port, None);
+
+        // Act
+        let outcome = email_client
+            .send_mail(&email("reject@example.com"), "Hi", "<p>Hi</p>", "Hi")
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_server_takes_too_long() {
+        // Arrange: a server that accepts connections but never greets
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let _connection = listener.accept().await;
+            tokio::time::sleep(std::time::Duration::from_secs(180)).await;
+        });
+        let email_client = email_client(port, None);
+
+        // Act
+        let outcome = email_client
+            .send_mail(&email("ursula@example.com"), "Hi", "<p>Hi</p>", "Hi")
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+}
@@ -0,0 +1,440 @@
+use crate::domain::{MergeValues, NewsletterTemplate, SubscriberEmail};
+use crate::email_client::{EmailClient, EmailHeader};
+use crate::preference_links::PreferenceLinks;
+use crate::suppression_list::suppression_reason;
+use anyhow::Context;
+use html_escape::encode_double_quoted_attribute;
+use rand::Rng;
+use sqlx::types::Uuid;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::time::Duration;
+use tracing::{field::display, Span};
+
+/// Queue one delivery per confirmed subscriber.
+#[tracing::instrument(skip_all)]
+pub async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id,
+            subscriber_email
+        )
+        SELECT $1, email
+        FROM subscriptions
+        WHERE status = 'confirmed'
+        "#,
+        newsletter_issue_id,
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+/// How failed deliveries are retried before being moved to the dead-letter table.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: i16,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with jitter: the delay doubles with every attempt,
+    /// is capped at `max_delay` and a random half of it is shaved off so that
+    /// retries for the same issue don't all hit the email API at once.
+    pub fn backoff(&self, n_attempts: i16) -> Duration {
+        let exponent = n_attempts.saturating_sub(1).clamp(0, 31) as u32;
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.max_delay);
+        let half = delay / 2;
+        let jitter = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+        half + Duration::from_millis(jitter)
+    }
+}
+
+/// Final outcome of a delivery, kept in `issue_delivery_log` once the task leaves the queue.
+/// Deliveries that run out of attempts end up in the dead-letter table instead.
+#[derive(Clone, Copy, Debug)]
+enum DeliveryOutcome {
+    Sent,
+    Skipped,
+}
+
+impl DeliveryOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryOutcome::Sent => "sent",
+            DeliveryOutcome::Skipped => "skipped",
+        }
+    }
+}
+
+struct Task {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_attempts: i16,
+}
+
+/// Deliver the next pending newsletter email, if there is one.
+/// Failed deliveries are rescheduled according to the retry policy.
+#[tracing::instrument(
+    skip_all,
+    fields(
+        newsletter_issue_id=tracing::field::Empty,
+        subscriber_email=tracing::field::Empty
+    ),
+    err
+)]
+pub async fn try_execute_task(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    retry_policy: &RetryPolicy,
+    base_url: &str,
+    preference_links: &PreferenceLinks,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let task = dequeue_task(pool).await?;
+    if task.is_none() {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    }
+    let (transaction, task) = task.unwrap();
+    Span::current()
+        .record("newsletter_issue_id", &display(task.newsletter_issue_id))
+        .record("subscriber_email", &display(&task.subscriber_email));
+    match deliver(pool, email_client, base_url, preference_links, &task).await {
+        Ok(outcome) => complete_task(transaction, &task, outcome).await?,
+        Err(e) => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to deliver issue to a confirmed subscriber.",
+            );
+            record_failed_attempt(transaction, &task, &format!("{:#}", e), retry_policy).await?;
+        }
+    }
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+/// Render the issue for the recipient and send it.
+/// An error means the attempt failed and is worth retrying.
+async fn deliver(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    preference_links: &PreferenceLinks,
+    task: &Task,
+) -> Result<DeliveryOutcome, anyhow::Error> {
+    let email = match SubscriberEmail::parse(task.subscriber_email.clone()) {
+        Ok(email) => email,
+        Err(e) => {
+            tracing::error!(
+                error.message = %e,
+                "Skipping a confirmed subscriber. Their stored contact details are invalid",
+            );
+            return Ok(DeliveryOutcome::Skipped);
+        }
+    };
+    let recipient = match get_recipient(pool, email.as_ref()).await? {
+        Some(recipient) => recipient,
+        None => {
+            tracing::warn!("Skipping a subscriber who is no longer confirmed");
+            return Ok(DeliveryOutcome::Skipped);
+        }
+    };
+    if let Some(reason) = suppression_reason(pool, email.as_ref()).await? {
+        tracing::warn!(?reason, "Skipping a subscriber whose address is suppressed");
+        return Ok(DeliveryOutcome::Skipped);
+    }
+    let issue = get_issue(pool, task.newsletter_issue_id).await?;
+
+    let unsubscribe_url = format!(
+        "{}/subscriptions/unsubscribe?unsubscribe_token={}",
+        base_url, recipient.unsubscribe_token
+    );
+    let values = MergeValues {
+        name: &recipient.name,
+        email: email.as_ref(),
+        unsubscribe_url: &unsubscribe_url,
+    };
+    let html_content = NewsletterTemplate::parse(&issue.html_content)
+        .map_err(anyhow::Error::msg)?
+        .render_html(&values);
+    let text_content = NewsletterTemplate::parse(&issue.text_content)
+        .map_err(anyhow::Error::msg)?
+        .render_text(&values);
+    let preferences_url = preference_links.url_for(recipient.id);
+    let html_content = format!(
+        "{}\n<p><a href=\"{}\">Manage your subscription</a></p>",
+        html_content,
+        encode_double_quoted_attribute(&preferences_url)
+    );
+    let text_content = format!(
+        "{}\n\nManage your subscription: {}",
+        text_content, preferences_url
+    );
+
+    // One-click unsubscribe, as described in RFC 8058
+    let headers = [
+        EmailHeader {
+            name: "List-Unsubscribe".into(),
+            value: format!("<{}>", unsubscribe_url),
+        },
+        EmailHeader {
+            name: "List-Unsubscribe-Post".into(),
+            value: "List-Unsubscribe=One-Click".into(),
+        },
+    ];
+    email_client
+        .send_mail_with_headers(&email, &issue.title, &html_content, &text_content, &headers)
+        .await
+        .context("Failed to send the issue")?;
+    Ok(DeliveryOutcome::Sent)
+}
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_task(pool: &PgPool) -> Result<Option<(PgTransaction, Task)>, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    // Rows locked by other workers are skipped, so tasks are never delivered twice
+    let r = sqlx::query_as!(
+        Task,
+        r#"
+        SELECT newsletter_issue_id, subscriber_email, n_attempts
+        FROM issue_delivery_queue
+        WHERE execute_after <= now()
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+    Ok(r.map(|task| (transaction, task)))
+}
+
+#[tracing::instrument(skip_all)]
+async fn complete_task(
+    mut transaction: PgTransaction,
+    task: &Task,
+    outcome: DeliveryOutcome,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_log (
+            newsletter_issue_id,
+            subscriber_email,
+            outcome,
+            recorded_at
+        )
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET outcome = EXCLUDED.outcome,
+            recorded_at = EXCLUDED.recorded_at
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        outcome.as_str()
+    )
+    .execute(&mut transaction)
+    .await?;
+    delete_task(transaction, task).await
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_task(mut transaction: PgTransaction, task: &Task) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE
+            newsletter_issue_id = $1 AND
+            subscriber_email = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Schedule another attempt for the task or, once it has run out of attempts,
+/// move it to the dead-letter table.
+#[tracing::instrument(skip_all, fields(n_attempts = task.n_attempts + 1))]
+async fn record_failed_attempt(
+    mut transaction: PgTransaction,
+    task: &Task,
+    error: &str,
+    retry_policy: &RetryPolicy,
+) -> Result<(), anyhow::Error> {
+    let n_attempts = task.n_attempts + 1;
+    if n_attempts >= retry_policy.max_attempts {
+        tracing::error!("Giving up on delivery, moving it to the dead-letter table");
+        sqlx::query!(
+            r#"
+            INSERT INTO issue_delivery_dead_letters (
+                newsletter_issue_id,
+                subscriber_email,
+                n_attempts,
+                last_error,
+                failed_at
+            )
+            VALUES ($1, $2, $3, $4, now())
+            ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+            SET n_attempts = EXCLUDED.n_attempts,
+                last_error = EXCLUDED.last_error,
+                failed_at = EXCLUDED.failed_at
+            "#,
+            task.newsletter_issue_id,
+            task.subscriber_email,
+            n_attempts,
+            error
+        )
+        .execute(&mut transaction)
+        .await?;
+        delete_task(transaction, task).await
+    } else {
+        let backoff = retry_policy.backoff(n_attempts);
+        sqlx::query!(
+            r#"
+            UPDATE issue_delivery_queue
+            SET n_attempts = $3,
+                last_error = $4,
+                execute_after = now() + $5 * interval '1 millisecond'
+            WHERE
+                newsletter_issue_id = $1 AND
+                subscriber_email = $2
+            "#,
+            task.newsletter_issue_id,
+            task.subscriber_email,
+            n_attempts,
+            error,
+            backoff.as_millis() as f64
+        )
+        .execute(&mut transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+}
+
+struct Recipient {
+    id: Uuid,
+    name: String,
+    unsubscribe_token: String,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_recipient(pool: &PgPool, email: &str) -> Result<Option<Recipient>, anyhow::Error> {
+    let recipient = sqlx::query_as!(
+        Recipient,
+        r#"
+        SELECT id, name, unsubscribe_token
+        FROM subscriptions
+        WHERE email = $1 AND status = 'confirmed'
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(recipient)
+}
+
+struct NewsletterIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+        SELECT title, text_content, html_content
+        FROM newsletter_issues
+        WHERE
+            newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(issue)
+}
+
+pub async fn run_worker_until_stopped(
+    pool: PgPool,
+    email_client: EmailClient,
+    retry_policy: RetryPolicy,
+    base_url: String,
+    preference_links: PreferenceLinks,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_execute_task(
+            &pool,
+            &email_client,
+            &retry_policy,
+            &base_url,
+            &preference_links,
+        )
+        .await
+        {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::issue_delivery_worker::RetryPolicy;
+    use std::time::Duration;
+
+    fn retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_with_every_attempt() {
+        let policy = retry_policy();
+        for n_attempts in 1..6 {
+            let expected = Duration::from_secs(2u64.pow(n_attempts as u32 - 1));
+            let backoff = policy.backoff(n_attempts);
+            assert!(
+                backoff >= expected / 2,
+                "{:?} < {:?}",
+                backoff,
+                expected / 2
+            );
+            assert!(backoff <= expected, "{:?} > {:?}", backoff, expected);
+        }
+    }
+
+    #[test]
+    fn backoff_is_capped_at_the_max_delay() {
+        let policy = retry_policy();
+        for n_attempts in [7, 20, i16::MAX] {
+            assert!(policy.backoff(n_attempts) <= policy.max_delay);
+        }
+    }
+}
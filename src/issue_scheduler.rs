@@ -0,0 +1,62 @@
+use crate::issue_delivery_worker::enqueue_delivery_tasks;
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::{field::display, Span};
+
+pub enum ReleaseOutcome {
+    IssueReleased,
+    NothingDue,
+}
+
+/// Release the next scheduled issue whose `send_at` has passed: its deliveries are
+/// queued for the issue delivery worker and the issue is marked as published.
+#[tracing::instrument(skip_all, fields(newsletter_issue_id=tracing::field::Empty), err)]
+pub async fn try_release_scheduled_issue(pool: &PgPool) -> Result<ReleaseOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let issue = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id
+        FROM newsletter_issues
+        WHERE status = 'scheduled' AND send_at <= now()
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+    let newsletter_issue_id = match issue {
+        Some(issue) => issue.newsletter_issue_id,
+        None => return Ok(ReleaseOutcome::NothingDue),
+    };
+    Span::current().record("newsletter_issue_id", &display(newsletter_issue_id));
+
+    enqueue_delivery_tasks(&mut transaction, newsletter_issue_id).await?;
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'published', published_at = now()
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+    tracing::info!("Released a scheduled newsletter issue");
+    Ok(ReleaseOutcome::IssueReleased)
+}
+
+pub async fn run_scheduler_until_stopped(pool: PgPool) -> Result<(), anyhow::Error> {
+    loop {
+        match try_release_scheduled_issue(&pool).await {
+            Ok(ReleaseOutcome::NothingDue) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ReleaseOutcome::IssueReleased) => {}
+        }
+    }
+}
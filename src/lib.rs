@@ -1,6 +1,15 @@
+pub mod authentication;
 pub mod configuration;
+pub mod consent_log;
 pub mod domain;
 pub mod email_client;
+pub mod idempotency;
+pub mod issue_delivery_worker;
+pub mod issue_scheduler;
+pub mod preference_links;
 pub mod routes;
 pub mod startup;
+pub mod subscriber_data;
+pub mod subscription_maintenance;
+pub mod suppression_list;
 pub mod telemetry;
@@ -3,7 +3,7 @@ use z2p::startup::Application;
 use z2p::telemetry::*;
 
 #[actix_web::main] // or #[tokio::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> anyhow::Result<()> {
     let tracing_subscriber = get_subscriber("Z2P".into(), "info".into());
     init_subscriber(tracing_subscriber);
 
@@ -0,0 +1,136 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// Signed, time-limited links giving a subscriber access to their preferences
+/// without a password. A token reads `<subscriber id>.<expiry>.<signature>`.
+#[derive(Clone)]
+pub struct PreferenceLinks {
+    base_url: String,
+    secret: String,
+    ttl: chrono::Duration,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum PreferenceLinkError {
+    #[error("This link is not valid.")]
+    Invalid,
+    #[error("This link has expired.")]
+    Expired,
+}
+
+impl PreferenceLinks {
+    pub fn new(base_url: String, secret: String, ttl: chrono::Duration) -> Self {
+        Self {
+            base_url,
+            secret,
+            ttl,
+        }
+    }
+
+    pub fn url_for(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/subscriptions/preferences?token={}",
+            self.base_url,
+            self.sign(subscriber_id, Utc::now() + self.ttl)
+        )
+    }
+
+    /// Return the subscriber the token was issued to.
+    pub fn verify(&self, token: &str) -> Result<Uuid, PreferenceLinkError> {
+        let (payload, signature) = token.rsplit_once('.').ok_or(PreferenceLinkError::Invalid)?;
+        let signature = hex::decode(signature).map_err(|_| PreferenceLinkError::Invalid)?;
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| PreferenceLinkError::Invalid)?;
+
+        let (subscriber_id, expires_at) = payload
+            .split_once('.')
+            .ok_or(PreferenceLinkError::Invalid)?;
+        let subscriber_id =
+            Uuid::parse_str(subscriber_id).map_err(|_| PreferenceLinkError::Invalid)?;
+        let expires_at: i64 = expires_at
+            .parse()
+            .map_err(|_| PreferenceLinkError::Invalid)?;
+        if expires_at < Utc::now().timestamp() {
+            return Err(PreferenceLinkError::Expired);
+        }
+        Ok(subscriber_id)
+    }
+
+    fn sign(&self, subscriber_id: Uuid, expires_at: DateTime<Utc>) -> String {
+        let payload = format!("{}.{}", subscriber_id, expires_at.timestamp());
+        let signature = hex::encode(self.mac(&payload).finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PreferenceLinkError, PreferenceLinks};
+    use chrono::{Duration, Utc};
+    use claim::{assert_err, assert_ok_eq};
+    use uuid::Uuid;
+
+    fn links(secret: &str) -> PreferenceLinks {
+        PreferenceLinks::new("http://localhost".into(), secret.into(), Duration::hours(1))
+    }
+
+    #[test]
+    fn a_signed_token_is_verified() {
+        let links = links("secret");
+        let subscriber_id = Uuid::new_v4();
+        let token = links.sign(subscriber_id, Utc::now() + Duration::hours(1));
+        assert_ok_eq!(links.verify(&token), subscriber_id);
+    }
+
+    #[test]
+    fn urls_carry_a_valid_token() {
+        let links = links("secret");
+        let subscriber_id = Uuid::new_v4();
+        let url = links.url_for(subscriber_id);
+        let token = url.split_once("token=").unwrap().1;
+        assert_ok_eq!(links.verify(token), subscriber_id);
+    }
+
+    #[test]
+    fn a_token_for_another_subscriber_is_rejected() {
+        let links = links("secret");
+        let token = links.sign(Uuid::new_v4(), Utc::now() + Duration::hours(1));
+        let (_, rest) = token.split_once('.').unwrap();
+        let forged = format!("{}.{}", Uuid::new_v4(), rest);
+        assert_eq!(links.verify(&forged), Err(PreferenceLinkError::Invalid));
+    }
+
+    #[test]
+    fn a_token_signed_with_another_secret_is_rejected() {
+        let token = links("another secret").sign(Uuid::new_v4(), Utc::now() + Duration::hours(1));
+        assert_eq!(
+            links("secret").verify(&token),
+            Err(PreferenceLinkError::Invalid)
+        );
+    }
+
+    #[test]
+    fn an_expired_token_is_rejected() {
+        let links = links("secret");
+        let token = links.sign(Uuid::new_v4(), Utc::now() - Duration::minutes(1));
+        assert_eq!(links.verify(&token), Err(PreferenceLinkError::Expired));
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        let links = links("secret");
+        for token in ["", "abc", "a.b.c", "a.b.zz"] {
+            assert_err!(links.verify(token));
+        }
+    }
+}
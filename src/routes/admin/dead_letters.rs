@@ -0,0 +1,90 @@
+use crate::routes::admin::{authenticate, AdminError};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+#[derive(serde::Serialize)]
+pub struct DeadLetter {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_attempts: i16,
+    last_error: String,
+    failed_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "List dead-lettered deliveries", skip(pool, request))]
+pub async fn list_dead_letters(
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AdminError> {
+    authenticate(&request, &pool).await?;
+    let dead_letters = sqlx::query_as!(
+        DeadLetter,
+        r#"
+        SELECT newsletter_issue_id, subscriber_email, n_attempts, last_error, failed_at
+        FROM issue_delivery_dead_letters
+        ORDER BY failed_at
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to fetch dead-lettered deliveries")?;
+    Ok(HttpResponse::Ok().json(dead_letters))
+}
+
+/// Requeue every dead letter of an issue, or a single one when `subscriber_email` is set.
+#[derive(serde::Deserialize)]
+pub struct RequeueData {
+    newsletter_issue_id: Uuid,
+    subscriber_email: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct RequeueOutcome {
+    requeued: u64,
+}
+
+#[tracing::instrument(
+    name = "Requeue dead-lettered deliveries",
+    skip(body, pool, request),
+    fields(newsletter_issue_id = %body.newsletter_issue_id)
+)]
+pub async fn requeue_dead_letters(
+    body: web::Json<RequeueData>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AdminError> {
+    authenticate(&request, &pool).await?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    // The deleted rows are fed straight back into the queue, with a fresh attempt budget
+    let requeued = sqlx::query!(
+        r#"
+        WITH requeued AS (
+            DELETE FROM issue_delivery_dead_letters
+            WHERE
+                newsletter_issue_id = $1 AND
+                ($2::TEXT IS NULL OR subscriber_email = $2)
+            RETURNING newsletter_issue_id, subscriber_email
+        )
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT newsletter_issue_id, subscriber_email FROM requeued
+        ON CONFLICT DO NOTHING
+        "#,
+        body.newsletter_issue_id,
+        body.subscriber_email.as_deref()
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to move dead-lettered deliveries back to the queue")?
+    .rows_affected();
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to requeue deliveries")?;
+    Ok(HttpResponse::Ok().json(RequeueOutcome { requeued }))
+}
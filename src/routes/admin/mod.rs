@@ -0,0 +1,76 @@
+mod dead_letters;
+mod subscriber_data;
+mod subscriber_export;
+mod subscriber_import;
+mod suppressions;
+
+pub use dead_letters::*;
+pub use subscriber_data::*;
+pub use subscriber_export::*;
+pub use subscriber_import::*;
+pub use suppressions::*;
+
+use crate::authentication::{basic_authentication, validate_credentials, AuthError};
+use crate::routes::error_chain_fmt;
+use actix_http::{header, StatusCode};
+use actix_web::http::header::HeaderValue;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+use std::fmt::{Debug, Formatter};
+
+#[derive(thiserror::Error)]
+pub enum AdminError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("Authentication Failed")]
+    AuthError(#[source] anyhow::Error),
+    #[error("{0}")]
+    ValidationError(String),
+}
+
+impl Debug for AdminError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for AdminError {
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            AdminError::UnexpectedError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AdminError::AuthError(_) => {
+                let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
+                let header_value = HeaderValue::from_str(r#"Basic realm="admin""#).unwrap();
+                response
+                    .headers_mut()
+                    .insert(header::WWW_AUTHENTICATE, header_value);
+                response
+            }
+            AdminError::ValidationError(message) => {
+                HttpResponse::build(StatusCode::BAD_REQUEST).body(message.clone())
+            }
+        }
+    }
+}
+
+/// Every admin endpoint requires the same 'Basic' credentials used to publish newsletters.
+#[tracing::instrument(
+    name = "Authenticate admin",
+    skip(request, pool),
+    fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
+)]
+async fn authenticate(request: &HttpRequest, pool: &PgPool) -> Result<Uuid, AdminError> {
+    let credentials = basic_authentication(request.headers())
+        .await
+        .map_err(AdminError::AuthError)?;
+    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    let user_id = validate_credentials(credentials, pool)
+        .await
+        .map_err(|e| match e {
+            AuthError::InvalidCredentials(_) => AdminError::AuthError(e.into()),
+            AuthError::UnexpectedError(_) => AdminError::UnexpectedError(e.into()),
+        })?;
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+    Ok(user_id)
+}
@@ -0,0 +1,38 @@
+use crate::routes::admin::{authenticate, AdminError};
+use crate::subscriber_data::{collect_subscriber_data, erase_subscriber};
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct SubscriberLookup {
+    email: String,
+}
+
+/// Everything stored about one subscriber, to answer a data access request.
+#[tracing::instrument(name = "Export subscriber data", skip(params, pool, request))]
+pub async fn get_subscriber_data(
+    params: web::Query<SubscriberLookup>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AdminError> {
+    authenticate(&request, &pool).await?;
+    match collect_subscriber_data(&pool, &params.email).await? {
+        Some(data) => Ok(HttpResponse::Ok().json(data)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// Erase everything stored about one subscriber, to answer an erasure request.
+#[tracing::instrument(name = "Erase subscriber data", skip(body, pool, request))]
+pub async fn erase_subscriber_data(
+    body: web::Json<SubscriberLookup>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AdminError> {
+    authenticate(&request, &pool).await?;
+    if erase_subscriber(&pool, &body.email).await? {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
@@ -0,0 +1,151 @@
+use crate::routes::admin::{authenticate, AdminError};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ExportParameters {
+    format: ExportFormat,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedSubscriber {
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    confirmed_at: Option<DateTime<Utc>>,
+    consent_source: Option<String>,
+    /// Taken from the consent log, empty for subscribers who never used the form.
+    signup_form: Option<String>,
+    consent_text_version: Option<String>,
+    signup_ip_address: Option<String>,
+    signup_user_agent: Option<String>,
+    confirmation_ip_address: Option<String>,
+    confirmation_user_agent: Option<String>,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "subscribers.csv",
+            ExportFormat::Json => "subscribers.json",
+        }
+    }
+
+    fn header(self) -> Bytes {
+        match self {
+            ExportFormat::Csv => Bytes::from_static(
+                b"email,name,status,subscribed_at,confirmed_at,consent_source,\
+                    signup_form,consent_text_version,signup_ip_address,signup_user_agent,\
+                    confirmation_ip_address,confirmation_user_agent\n",
+            ),
+            ExportFormat::Json => Bytes::from_static(b"["),
+        }
+    }
+
+    fn footer(self) -> Bytes {
+        match self {
+            ExportFormat::Csv => Bytes::new(),
+            ExportFormat::Json => Bytes::from_static(b"]"),
+        }
+    }
+
+    fn row(self, subscriber: &ExportedSubscriber, is_first: bool) -> Result<Bytes, anyhow::Error> {
+        match self {
+            ExportFormat::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(vec![]);
+                writer.serialize(subscriber)?;
+                Ok(writer.into_inner()?.into())
+            }
+            ExportFormat::Json => {
+                let mut row = if is_first { vec![] } else { vec![b','] };
+                serde_json::to_writer(&mut row, subscriber)?;
+                Ok(row.into())
+            }
+        }
+    }
+}
+
+/// Stream every subscriber, one row at a time: the table is never loaded in memory.
+#[tracing::instrument(name = "Export subscribers", skip(params, pool, request))]
+pub async fn export_subscribers(
+    params: web::Query<ExportParameters>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AdminError> {
+    authenticate(&request, &pool).await?;
+    let format = params.format;
+    let pool = pool.into_inner();
+    let body = async_stream::try_stream! {
+        yield format.header();
+        let mut subscribers = sqlx::query_as!(
+            ExportedSubscriber,
+            r#"
+            SELECT
+                s.email, s.name, s.status, s.subscribed_at, s.confirmed_at, s.consent_source,
+                signup.source AS "signup_form?",
+                COALESCE(confirmation.consent_text_version, signup.consent_text_version)
+                    AS "consent_text_version?",
+                signup.ip_address AS "signup_ip_address?",
+                signup.user_agent AS "signup_user_agent?",
+                confirmation.ip_address AS "confirmation_ip_address?",
+                confirmation.user_agent AS "confirmation_user_agent?"
+            FROM subscriptions s
+            LEFT JOIN LATERAL (
+                SELECT source, consent_text_version, ip_address, user_agent
+                FROM subscription_consents
+                WHERE subscriber_id = s.id AND event = 'subscribed'
+                ORDER BY recorded_at DESC
+                LIMIT 1
+            ) signup ON true
+            LEFT JOIN LATERAL (
+                SELECT consent_text_version, ip_address, user_agent
+                FROM subscription_consents
+                WHERE subscriber_id = s.id AND event = 'confirmed'
+                ORDER BY recorded_at DESC
+                LIMIT 1
+            ) confirmation ON true
+            ORDER BY s.subscribed_at, s.id
+            "#
+        )
+        .fetch(pool.as_ref());
+        let mut is_first = true;
+        while let Some(subscriber) = subscribers.try_next().await.map_err(anyhow::Error::from)? {
+            yield format.row(&subscriber, is_first)?;
+            is_first = false;
+        }
+        yield format.footer();
+    };
+    // An error halfway through can only abort the response, it is logged here
+    let body = body.map_err(|e: anyhow::Error| {
+        tracing::error!(error.cause_chain = ?e, "Failed to export subscribers");
+        actix_web::error::ErrorInternalServerError(e)
+    });
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format.file_name().into())],
+        })
+        .streaming(body))
+}
@@ -0,0 +1,242 @@
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::routes::admin::{authenticate, AdminError};
+use crate::routes::generate_subscription_token;
+use crate::suppression_list::{suppression_reason, SuppressionReason};
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::types::Uuid;
+use sqlx::{PgPool, Postgres, Transaction};
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    /// The subscribers already agreed to receive the newsletter elsewhere.
+    Confirmed,
+    /// The subscribers are sent a confirmation email in the background, as if they used the form.
+    Pending,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ImportParameters {
+    status: ImportStatus,
+    /// Where confirmed subscribers gave their consent, e.g. the name of the previous provider.
+    consent_source: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct CsvRow {
+    email: String,
+    name: String,
+}
+
+#[derive(serde::Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum RowOutcome {
+    Accepted,
+    Duplicate,
+    Erased,
+    Invalid,
+}
+
+#[derive(serde::Serialize)]
+struct RowReport {
+    line: u64,
+    email: Option<String>,
+    outcome: RowOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct ImportReport {
+    accepted: usize,
+    duplicate: usize,
+    erased: usize,
+    invalid: usize,
+    rows: Vec<RowReport>,
+}
+
+/// Import subscribers from a CSV body with an `email,name` header row.
+/// Every row is reported as accepted, duplicate (already on the list or earlier
+/// in the file), erased (the subscriber asked for their data to be erased) or invalid.
+#[tracing::instrument(name = "Import subscribers", skip(body, params, pool, request))]
+pub async fn import_subscribers(
+    body: Bytes,
+    params: web::Query<ImportParameters>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AdminError> {
+    authenticate(&request, &pool).await?;
+    let consent_source = match (params.status, params.consent_source.as_deref()) {
+        (ImportStatus::Confirmed, None | Some("")) => {
+            return Err(AdminError::ValidationError(
+                "A consent_source is required to import confirmed subscribers".into(),
+            ))
+        }
+        (_, consent_source) => consent_source,
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body.as_ref());
+    let headers = reader
+        .headers()
+        .map_err(|e| AdminError::ValidationError(format!("Failed to read the CSV: {}", e)))?
+        .clone();
+    if !(headers.iter().any(|h| h == "email") && headers.iter().any(|h| h == "name")) {
+        return Err(AdminError::ValidationError(
+            "The CSV must start with an `email,name` header row".into(),
+        ));
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let mut rows = vec![];
+    for record in reader.records() {
+        let parsed = record.and_then(|record| {
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            record
+                .deserialize::<CsvRow>(Some(&headers))
+                .map(|row| (line, row))
+        });
+        let (line, row) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                rows.push(RowReport {
+                    line: e.position().map(|p| p.line()).unwrap_or_default(),
+                    email: None,
+                    outcome: RowOutcome::Invalid,
+                    error: Some(e.to_string()),
+                });
+                continue;
+            }
+        };
+        let email = row.email.clone();
+        let new_subscriber = match parse_row(row) {
+            Ok(new_subscriber) => new_subscriber,
+            Err(e) => {
+                rows.push(RowReport {
+                    line,
+                    email: Some(email),
+                    outcome: RowOutcome::Invalid,
+                    error: Some(e),
+                });
+                continue;
+            }
+        };
+        // Other suppressed addresses are imported, they are simply never mailed
+        let reason = suppression_reason(&mut transaction, new_subscriber.email.as_ref())
+            .await
+            .context("Failed to check the suppression list")?;
+        if reason == Some(SuppressionReason::Erased) {
+            rows.push(RowReport {
+                line,
+                email: Some(email),
+                outcome: RowOutcome::Erased,
+                error: None,
+            });
+            continue;
+        }
+        let subscriber_id = import_subscriber(
+            &mut transaction,
+            &new_subscriber,
+            params.status,
+            consent_source,
+        )
+        .await
+        .context("Failed to insert an imported subscriber")?;
+        let outcome = match subscriber_id {
+            None => RowOutcome::Duplicate,
+            Some(subscriber_id) => {
+                if params.status == ImportStatus::Pending {
+                    enqueue_confirmation_email(&mut transaction, subscriber_id)
+                        .await
+                        .context("Failed to enqueue a confirmation email")?;
+                }
+                RowOutcome::Accepted
+            }
+        };
+        rows.push(RowReport {
+            line,
+            email: Some(email),
+            outcome,
+            error: None,
+        });
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to import subscribers")?;
+
+    let count = |outcome: RowOutcome| rows.iter().filter(|r| r.outcome == outcome).count();
+    let report = ImportReport {
+        accepted: count(RowOutcome::Accepted),
+        duplicate: count(RowOutcome::Duplicate),
+        erased: count(RowOutcome::Erased),
+        invalid: count(RowOutcome::Invalid),
+        rows,
+    };
+    Ok(HttpResponse::Ok().json(report))
+}
+
+fn parse_row(row: CsvRow) -> Result<NewSubscriber, String> {
+    let email = SubscriberEmail::parse(row.email.clone())
+        .map_err(|e| format!("The email {:?} {}", row.email, e))?;
+    let name = SubscriberName::parse(row.name.clone())
+        .map_err(|e| format!("The name {:?} {}", row.name, e))?;
+    Ok(NewSubscriber { email, name })
+}
+
+/// Returns `None` if the email is already on the list.
+async fn import_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    new_subscriber: &NewSubscriber,
+    status: ImportStatus,
+    consent_source: Option<&str>,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let now = Utc::now();
+    let (status, confirmed_at) = match status {
+        ImportStatus::Confirmed => ("confirmed", Some(now)),
+        ImportStatus::Pending => ("pending_confirmation", None),
+    };
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions
+            (id, email, name, subscribed_at, status, unsubscribe_token, consent_source, confirmed_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (email) DO NOTHING
+        RETURNING id
+        "#,
+        Uuid::new_v4(),
+        new_subscriber.email.as_ref(),
+        new_subscriber.name.as_ref(),
+        now,
+        status,
+        generate_subscription_token(),
+        consent_source,
+        confirmed_at
+    )
+    .fetch_optional(transaction)
+    .await?;
+    Ok(result.map(|r| r.id))
+}
+
+async fn enqueue_confirmation_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO confirmation_email_queue (subscriber_id, enqueued_at)
+        VALUES ($1, now())
+        "#,
+        subscriber_id
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use crate::domain::SubscriberEmail;
+use crate::routes::admin::{authenticate, AdminError};
+use crate::suppression_list::{list_suppressed_emails, suppress, unsuppress, SuppressionReason};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+
+#[tracing::instrument(name = "List suppressed emails", skip(pool, request))]
+pub async fn list_suppressions(
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AdminError> {
+    authenticate(&request, &pool).await?;
+    let suppressed_emails = list_suppressed_emails(pool.get_ref())
+        .await
+        .context("Failed to fetch the suppression list")?;
+    Ok(HttpResponse::Ok().json(suppressed_emails))
+}
+
+/// Add an address to the suppression list, `reason` defaults to `manual`.
+#[derive(serde::Deserialize)]
+pub struct NewSuppression {
+    email: String,
+    reason: Option<SuppressionReason>,
+}
+
+#[tracing::instrument(name = "Suppress an email", skip(body, pool, request))]
+pub async fn add_suppression(
+    body: web::Json<NewSuppression>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AdminError> {
+    authenticate(&request, &pool).await?;
+    let email = SubscriberEmail::parse(body.0.email)
+        .map_err(|e| AdminError::ValidationError(format!("The email {}", e)))?;
+    let reason = match body.0.reason {
+        Some(SuppressionReason::Erased) => {
+            return Err(AdminError::ValidationError(
+                "Erase the subscriber's data to suppress their address for that reason".into(),
+            ))
+        }
+        reason => reason.unwrap_or(SuppressionReason::Manual),
+    };
+    suppress(pool.get_ref(), email.as_ref(), reason)
+        .await
+        .context("Failed to suppress an email")?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(serde::Deserialize)]
+pub struct SuppressionRemoval {
+    email: String,
+}
+
+#[tracing::instrument(name = "Remove a suppressed email", skip(body, pool, request))]
+pub async fn remove_suppression(
+    body: web::Json<SuppressionRemoval>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AdminError> {
+    authenticate(&request, &pool).await?;
+    let removed = unsuppress(pool.get_ref(), &body.email)
+        .await
+        .context("Failed to remove a suppressed email")?;
+    if removed {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
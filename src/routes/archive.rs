@@ -0,0 +1,175 @@
+use crate::domain::{MergeValues, NewsletterTemplate};
+use crate::routes::error_chain_fmt;
+use actix_http::StatusCode;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use html_escape::encode_text;
+use sqlx::PgPool;
+use std::fmt::{Debug, Formatter, Write};
+
+const ISSUES_PER_PAGE: i64 = 20;
+
+#[derive(thiserror::Error)]
+pub enum ArchiveError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("There is no published issue at this address")]
+    NotFound,
+}
+
+impl Debug for ArchiveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ArchiveError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ArchiveError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ArchiveError::NotFound => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct ArchiveParameters {
+    page: Option<u32>,
+}
+
+struct ArchivedIssue {
+    slug: String,
+    title: String,
+    published_at: Option<DateTime<Utc>>,
+}
+
+/// List published issues, most recent first.
+#[tracing::instrument(name = "List archived issues", skip(params, pool))]
+pub async fn archive(
+    params: web::Query<ArchiveParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ArchiveError> {
+    let page = params.page.unwrap_or(1).max(1);
+    let offset = (page as i64 - 1) * ISSUES_PER_PAGE;
+    // Fetch one extra row to know whether there is a next page
+    let mut issues = sqlx::query_as!(
+        ArchivedIssue,
+        r#"
+        SELECT slug, title, published_at
+        FROM newsletter_issues
+        WHERE status = 'published' AND published_at <= now()
+        ORDER BY published_at DESC, newsletter_issue_id
+        LIMIT $1 OFFSET $2
+        "#,
+        ISSUES_PER_PAGE + 1,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to fetch archived issues")?;
+    let has_next_page = issues.len() as i64 > ISSUES_PER_PAGE;
+    issues.truncate(ISSUES_PER_PAGE as usize);
+
+    let mut issue_list = String::new();
+    for issue in issues {
+        let published_at = issue
+            .published_at
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        writeln!(
+            issue_list,
+            r#"<li><a href="/archive/{}">{}</a> ({})</li>"#,
+            issue.slug,
+            encode_text(&issue.title),
+            published_at
+        )
+        .unwrap();
+    }
+    let mut pagination = String::new();
+    if page > 1 {
+        write!(
+            pagination,
+            r#"<a href="/archive?page={}">Newer issues</a> "#,
+            page - 1
+        )
+        .unwrap();
+    }
+    if has_next_page {
+        write!(
+            pagination,
+            r#"<a href="/archive?page={}">Older issues</a>"#,
+            page + 1
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Newsletter archive</title>
+</head>
+<body>
+    <h1>Newsletter archive</h1>
+    <ul>
+{}    </ul>
+    <p>{}</p>
+</body>
+</html>"#,
+            issue_list, pagination
+        )))
+}
+
+/// Render the HTML body of a published issue.
+#[tracing::instrument(name = "Get archived issue", skip(pool))]
+pub async fn archived_issue(
+    slug: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ArchiveError> {
+    let issue = sqlx::query!(
+        r#"
+        SELECT title, html_content
+        FROM newsletter_issues
+        WHERE slug = $1 AND status = 'published' AND published_at <= now()
+        "#,
+        slug.as_str()
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to fetch archived issue")?
+    .ok_or(ArchiveError::NotFound)?;
+    // Merge fields are filled in for each recipient when the issue is delivered,
+    // the archive is not addressed to anyone in particular.
+    let values = MergeValues {
+        name: "reader",
+        email: "",
+        unsubscribe_url: "#",
+    };
+    let html_content = NewsletterTemplate::parse(&issue.html_content)
+        .map(|template| template.render_html(&values))
+        .unwrap_or(issue.html_content);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>{title}</title>
+</head>
+<body>
+    <p><a href="/archive">Back to the archive</a></p>
+    <h1>{title}</h1>
+    {}
+</body>
+</html>"#,
+            html_content,
+            title = encode_text(&issue.title),
+        )))
+}
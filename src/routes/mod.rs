@@ -1,9 +1,19 @@
+mod admin;
+mod archive;
 mod health_check;
-mod newsletter;
+mod newsletters;
+mod preferences;
 mod subscription_confirm;
+mod subscription_unsubscribe;
 mod subscriptions;
+mod webhooks;
 
+pub use admin::*;
+pub use archive::*;
 pub use health_check::*;
-pub use newsletter::*;
+pub use newsletters::*;
+pub use preferences::*;
 pub use subscription_confirm::*;
+pub use subscription_unsubscribe::*;
 pub use subscriptions::*;
+pub use webhooks::*;
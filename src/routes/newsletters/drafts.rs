@@ -0,0 +1,245 @@
+use crate::issue_delivery_worker::enqueue_delivery_tasks;
+use crate::routes::newsletters::{authenticate, Content, PublishError};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct DraftData {
+    title: String,
+    content: Content,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PublishDraftData {
+    send_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize)]
+struct DraftCreated {
+    newsletter_issue_id: Uuid,
+}
+
+#[derive(serde::Serialize)]
+struct DraftSummary {
+    newsletter_issue_id: Uuid,
+    title: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct Draft {
+    newsletter_issue_id: Uuid,
+    title: String,
+    content: Content,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+fn draft_not_found(newsletter_issue_id: Uuid) -> PublishError {
+    PublishError::NotFound(format!("No draft with id {}", newsletter_issue_id))
+}
+
+#[tracing::instrument(name = "Create a newsletter draft", skip(body, pool, request))]
+pub async fn create_draft(
+    body: web::Json<DraftData>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, PublishError> {
+    let user_id = authenticate(&request, &pool).await?;
+    body.content.validate()?;
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            user_id,
+            title,
+            text_content,
+            html_content,
+            status
+        )
+        VALUES ($1, $2, $3, $4, $5, 'draft')
+        "#,
+        newsletter_issue_id,
+        user_id,
+        body.title,
+        body.content.text,
+        body.content.html
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to store newsletter draft")?;
+    Ok(HttpResponse::Created().json(DraftCreated {
+        newsletter_issue_id,
+    }))
+}
+
+#[tracing::instrument(name = "List newsletter drafts", skip(pool, request))]
+pub async fn list_drafts(
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, PublishError> {
+    let user_id = authenticate(&request, &pool).await?;
+    let drafts = sqlx::query_as!(
+        DraftSummary,
+        r#"
+        SELECT newsletter_issue_id, title, created_at, updated_at
+        FROM newsletter_issues
+        WHERE user_id = $1 AND status = 'draft'
+        ORDER BY updated_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to fetch newsletter drafts")?;
+    Ok(HttpResponse::Ok().json(drafts))
+}
+
+#[tracing::instrument(name = "Get a newsletter draft", skip(pool, request))]
+pub async fn get_draft(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, PublishError> {
+    let user_id = authenticate(&request, &pool).await?;
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let draft = sqlx::query!(
+        r#"
+        SELECT title, text_content, html_content, created_at, updated_at
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND user_id = $2 AND status = 'draft'
+        "#,
+        newsletter_issue_id,
+        user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to fetch newsletter draft")?
+    .ok_or_else(|| draft_not_found(newsletter_issue_id))?;
+    Ok(HttpResponse::Ok().json(Draft {
+        newsletter_issue_id,
+        title: draft.title,
+        content: Content {
+            text: draft.text_content,
+            html: draft.html_content,
+        },
+        created_at: draft.created_at,
+        updated_at: draft.updated_at,
+    }))
+}
+
+#[tracing::instrument(name = "Update a newsletter draft", skip(body, pool, request))]
+pub async fn update_draft(
+    newsletter_issue_id: web::Path<Uuid>,
+    body: web::Json<DraftData>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, PublishError> {
+    let user_id = authenticate(&request, &pool).await?;
+    body.content.validate()?;
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET title = $3, text_content = $4, html_content = $5, updated_at = now()
+        WHERE newsletter_issue_id = $1 AND user_id = $2 AND status = 'draft'
+        "#,
+        newsletter_issue_id,
+        user_id,
+        body.title,
+        body.content.text,
+        body.content.html
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to update newsletter draft")?
+    .rows_affected();
+    if n_updated_rows == 0 {
+        return Err(draft_not_found(newsletter_issue_id));
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(name = "Delete a newsletter draft", skip(pool, request))]
+pub async fn delete_draft(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, PublishError> {
+    let user_id = authenticate(&request, &pool).await?;
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let n_deleted_rows = sqlx::query!(
+        r#"
+        DELETE FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND user_id = $2 AND status = 'draft'
+        "#,
+        newsletter_issue_id,
+        user_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to delete newsletter draft")?
+    .rows_affected();
+    if n_deleted_rows == 0 {
+        return Err(draft_not_found(newsletter_issue_id));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Hand a draft over to the regular delivery path: it is either queued straight
+/// away or, with a `send_at` in the future, left to the issue scheduler.
+#[tracing::instrument(name = "Publish a newsletter draft", skip(body, pool, request))]
+pub async fn publish_draft(
+    newsletter_issue_id: web::Path<Uuid>,
+    body: Option<web::Json<PublishDraftData>>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, PublishError> {
+    let user_id = authenticate(&request, &pool).await?;
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let send_at = body
+        .and_then(|body| body.send_at)
+        .filter(|send_at| *send_at > Utc::now());
+    let (status, published_at) = match send_at {
+        Some(_) => ("scheduled", None),
+        None => ("published", Some(Utc::now())),
+    };
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = $3, send_at = $4, published_at = $5, updated_at = now()
+        WHERE newsletter_issue_id = $1 AND user_id = $2 AND status = 'draft'
+        "#,
+        newsletter_issue_id,
+        user_id,
+        status,
+        send_at,
+        published_at
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to publish newsletter draft")?
+    .rows_affected();
+    if n_updated_rows == 0 {
+        return Err(draft_not_found(newsletter_issue_id));
+    }
+    if send_at.is_none() {
+        enqueue_delivery_tasks(&mut transaction, newsletter_issue_id)
+            .await
+            .context("Failed to enqueue delivery tasks")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to publish a newsletter draft")?;
+    Ok(HttpResponse::Accepted().finish())
+}
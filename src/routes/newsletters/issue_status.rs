@@ -0,0 +1,88 @@
+use crate::routes::newsletters::{authenticate, PublishError};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+#[derive(serde::Serialize)]
+pub struct IssueStatus {
+    newsletter_issue_id: Uuid,
+    title: String,
+    author: Option<Uuid>,
+    status: String,
+    created_at: DateTime<Utc>,
+    send_at: Option<DateTime<Utc>>,
+    published_at: Option<DateTime<Utc>>,
+    deliveries: DeliveryCounts,
+}
+
+/// `failed` counts the deliveries that ran out of attempts and were dead-lettered,
+/// deliveries waiting for a retry are still `queued`.
+#[derive(serde::Serialize)]
+pub struct DeliveryCounts {
+    queued: i64,
+    sent: i64,
+    failed: i64,
+    skipped: i64,
+}
+
+#[tracing::instrument(name = "Get newsletter issue status", skip(pool, request))]
+pub async fn get_issue_status(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, PublishError> {
+    authenticate(&request, &pool).await?;
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let status = sqlx::query!(
+        r#"
+        SELECT
+            i.title,
+            i.user_id,
+            i.status,
+            i.created_at,
+            i.send_at,
+            i.published_at,
+            (
+                SELECT COUNT(*) FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = i.newsletter_issue_id
+            ) as "queued!",
+            (
+                SELECT COUNT(*) FROM issue_delivery_log l
+                WHERE l.newsletter_issue_id = i.newsletter_issue_id AND l.outcome = 'sent'
+            ) as "sent!",
+            (
+                SELECT COUNT(*) FROM issue_delivery_dead_letters d
+                WHERE d.newsletter_issue_id = i.newsletter_issue_id
+            ) as "failed!",
+            (
+                SELECT COUNT(*) FROM issue_delivery_log l
+                WHERE l.newsletter_issue_id = i.newsletter_issue_id AND l.outcome = 'skipped'
+            ) as "skipped!"
+        FROM newsletter_issues i
+        WHERE i.newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to fetch the newsletter issue status")?
+    .ok_or_else(|| PublishError::NotFound(format!("No issue with id {}", newsletter_issue_id)))?;
+
+    Ok(HttpResponse::Ok().json(IssueStatus {
+        newsletter_issue_id,
+        title: status.title,
+        author: status.user_id,
+        status: status.status,
+        created_at: status.created_at,
+        send_at: status.send_at,
+        published_at: status.published_at,
+        deliveries: DeliveryCounts {
+            queued: status.queued,
+            sent: status.sent,
+            failed: status.failed,
+            skipped: status.skipped,
+        },
+    }))
+}
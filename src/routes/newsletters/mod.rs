@@ -0,0 +1,129 @@
+mod drafts;
+mod issue_status;
+mod publish;
+mod schedule;
+
+pub use drafts::*;
+pub use issue_status::*;
+pub use publish::*;
+pub use schedule::*;
+
+use crate::authentication::{basic_authentication, validate_credentials, AuthError};
+use crate::domain::{markdown_to_html, markdown_to_text, NewsletterTemplate};
+use crate::routes::error_chain_fmt;
+use actix_http::{header, StatusCode};
+use actix_web::http::header::HeaderValue;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+use std::fmt::{Debug, Formatter};
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "ContentForm")]
+pub struct Content {
+    text: String,
+    html: String,
+}
+
+/// Content is submitted either as separate text and html bodies or as a single
+/// markdown body that both are rendered from.
+#[derive(serde::Deserialize)]
+struct ContentForm {
+    text: Option<String>,
+    html: Option<String>,
+    markdown: Option<String>,
+}
+
+impl TryFrom<ContentForm> for Content {
+    type Error = String;
+
+    fn try_from(form: ContentForm) -> Result<Self, Self::Error> {
+        match form {
+            ContentForm {
+                text: None,
+                html: None,
+                markdown: Some(markdown),
+            } => Ok(Self {
+                text: markdown_to_text(&markdown),
+                html: markdown_to_html(&markdown),
+            }),
+            ContentForm {
+                text: Some(text),
+                html: Some(html),
+                markdown: None,
+            } => Ok(Self { text, html }),
+            _ => Err("Content must have either a `markdown` body or both `text` and `html`".into()),
+        }
+    }
+}
+
+impl Content {
+    /// Reject content using merge fields we don't know how to fill in.
+    fn validate(&self) -> Result<(), PublishError> {
+        NewsletterTemplate::parse(&self.text)
+            .and_then(|_| NewsletterTemplate::parse(&self.html))
+            .map(|_| ())
+            .map_err(PublishError::ValidationError)
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum PublishError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("Authentication Failed")]
+    AuthError(#[source] anyhow::Error),
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+}
+
+impl Debug for PublishError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for PublishError {
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            PublishError::UnexpectedError(_) => {
+                HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            PublishError::AuthError(_) => {
+                let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
+                let header_value = HeaderValue::from_str(r#"Basic realm="publish""#).unwrap();
+                response
+                    .headers_mut()
+                    .insert(header::WWW_AUTHENTICATE, header_value);
+                response
+            }
+            PublishError::ValidationError(_) => HttpResponse::new(StatusCode::BAD_REQUEST),
+            PublishError::NotFound(_) => HttpResponse::new(StatusCode::NOT_FOUND),
+            PublishError::Conflict(_) => HttpResponse::new(StatusCode::CONFLICT),
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "Authenticate editor",
+    skip(request, pool),
+    fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
+)]
+async fn authenticate(request: &HttpRequest, pool: &PgPool) -> Result<Uuid, PublishError> {
+    let credentials = basic_authentication(request.headers())
+        .await
+        .map_err(PublishError::AuthError)?;
+    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    let user_id = validate_credentials(credentials, pool)
+        .await
+        .map_err(|e| match e {
+            AuthError::InvalidCredentials(_) => PublishError::AuthError(e.into()),
+            AuthError::UnexpectedError(_) => PublishError::UnexpectedError(e.into()),
+        })?;
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+    Ok(user_id)
+}
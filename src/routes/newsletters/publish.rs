@@ -0,0 +1,143 @@
+use crate::idempotency::{save_response, try_processing, IdempotencyKey, NextAction};
+use crate::issue_delivery_worker::enqueue_delivery_tasks;
+use crate::routes::newsletters::{authenticate, Content, PublishError};
+use actix_http::header::HeaderMap;
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use sqlx::{PgPool, Postgres, Transaction};
+
+#[derive(serde::Deserialize)]
+pub struct BodyData {
+    title: String,
+    content: Content,
+    /// Hold the issue until this time instead of sending it straight away
+    send_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize)]
+struct PublishOutcome {
+    newsletter_issue_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    send_at: Option<DateTime<Utc>>,
+}
+
+#[tracing::instrument(name = "Publish a newsletter", skip(body, pool, request))]
+pub async fn publish_newsletter(
+    body: web::Json<BodyData>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, PublishError> {
+    let user_id = authenticate(&request, &pool).await?;
+    body.content.validate()?;
+    let idempotency_key = get_idempotency_key(request.headers())?;
+    let mut transaction = match &idempotency_key {
+        Some(idempotency_key) => match try_processing(&pool, idempotency_key, user_id).await? {
+            NextAction::StartProcessing(transaction) => transaction,
+            NextAction::ReturnSavedResponse(saved_response) => return Ok(saved_response),
+        },
+        None => pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")?,
+    };
+    // A `send_at` in the past means "send it now"
+    let send_at = body.send_at.filter(|send_at| *send_at > Utc::now());
+    let issue_id = insert_newsletter_issue(
+        &mut transaction,
+        user_id,
+        &body.title,
+        &body.content.text,
+        &body.content.html,
+        send_at,
+    )
+    .await
+    .context("Failed to store newsletter issue details")?;
+    if send_at.is_none() {
+        enqueue_delivery_tasks(&mut transaction, issue_id)
+            .await
+            .context("Failed to enqueue delivery tasks")?;
+    }
+    // Delivery happens in the background, see `issue_delivery_worker` and `issue_scheduler`.
+    // Progress can be followed on `GET /newsletters/{newsletter_issue_id}`.
+    let response = HttpResponse::Accepted().json(PublishOutcome {
+        newsletter_issue_id: issue_id,
+        send_at,
+    });
+    match idempotency_key {
+        Some(idempotency_key) => {
+            let response = save_response(transaction, &idempotency_key, user_id, response).await?;
+            Ok(response)
+        }
+        None => {
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit the SQL transaction to publish a newsletter")?;
+            Ok(response)
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+async fn insert_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    title: &str,
+    text_content: &str,
+    html_content: &str,
+    send_at: Option<DateTime<Utc>>,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    let (status, published_at) = match send_at {
+        Some(_) => ("scheduled", None),
+        None => ("published", Some(Utc::now())),
+    };
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            user_id,
+            title,
+            text_content,
+            html_content,
+            status,
+            send_at,
+            published_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+        newsletter_issue_id,
+        user_id,
+        title,
+        text_content,
+        html_content,
+        status,
+        send_at,
+        published_at
+    )
+    .execute(transaction)
+    .await?;
+    Ok(newsletter_issue_id)
+}
+
+/// The `Idempotency-Key` header is optional: requests without it are processed
+/// every time they are received.
+fn get_idempotency_key(headers: &HeaderMap) -> Result<Option<IdempotencyKey>, PublishError> {
+    let header = match headers.get("Idempotency-Key") {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let key = header
+        .to_str()
+        .map_err(|_| {
+            PublishError::ValidationError(
+                "The 'Idempotency-Key' header was not a valid UTF-8 string".into(),
+            )
+        })?
+        .to_string()
+        .try_into()
+        .map_err(PublishError::ValidationError)?;
+    Ok(Some(key))
+}
@@ -0,0 +1,101 @@
+use crate::routes::newsletters::{authenticate, PublishError};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct ScheduleData {
+    send_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Cancel a scheduled newsletter issue", skip(pool, request))]
+pub async fn cancel_scheduled_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, PublishError> {
+    authenticate(&request, &pool).await?;
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'cancelled'
+        WHERE
+            newsletter_issue_id = $1 AND
+            status = 'scheduled' AND
+            send_at > now()
+        "#,
+        newsletter_issue_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to cancel a scheduled newsletter issue")?
+    .rows_affected();
+    if n_updated_rows == 0 {
+        return Err(not_scheduled_error(&pool, newsletter_issue_id).await?);
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(
+    name = "Reschedule a newsletter issue",
+    skip(body, pool, request),
+    fields(send_at = %body.send_at)
+)]
+pub async fn reschedule_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    body: web::Json<ScheduleData>,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, PublishError> {
+    authenticate(&request, &pool).await?;
+    if body.send_at <= Utc::now() {
+        return Err(PublishError::ValidationError(
+            "An issue can only be rescheduled to a time in the future".into(),
+        ));
+    }
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let n_updated_rows = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET send_at = $2
+        WHERE
+            newsletter_issue_id = $1 AND
+            status = 'scheduled' AND
+            send_at > now()
+        "#,
+        newsletter_issue_id,
+        body.send_at
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to reschedule a newsletter issue")?
+    .rows_affected();
+    if n_updated_rows == 0 {
+        return Err(not_scheduled_error(&pool, newsletter_issue_id).await?);
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Tell apart unknown issues from issues that were already released or cancelled.
+async fn not_scheduled_error(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<PublishError, anyhow::Error> {
+    let issue = sqlx::query!(
+        r#"SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1"#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the newsletter issue status")?;
+    Ok(match issue {
+        None => PublishError::NotFound(format!("No issue with id {}", newsletter_issue_id)),
+        Some(issue) => PublishError::Conflict(format!(
+            "The issue is {} and can no longer be changed",
+            issue.status
+        )),
+    })
+}
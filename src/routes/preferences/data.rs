@@ -0,0 +1,55 @@
+use crate::preference_links::PreferenceLinks;
+use crate::routes::preferences::{authenticate, PreferencesError, TokenForm, TokenParameters};
+use crate::subscriber_data::{collect_subscriber_data, erase_subscriber};
+use actix_web::http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+/// Let subscribers download everything stored about them.
+#[tracing::instrument(name = "Download subscriber data", skip_all)]
+pub async fn download_subscriber_data(
+    params: web::Query<TokenParameters>,
+    pool: web::Data<PgPool>,
+    links: web::Data<PreferenceLinks>,
+) -> Result<HttpResponse, PreferencesError> {
+    let subscriber_id = authenticate(&links, &pool, &params.token).await?;
+    let email = get_email(&pool, subscriber_id).await?;
+    let data = collect_subscriber_data(&pool, &email)
+        .await?
+        .context("The subscriber disappeared while collecting their data")?;
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("my-subscription.json".into())],
+        })
+        .json(data))
+}
+
+/// Let subscribers erase everything stored about them.
+/// Their preference link stops working straight away.
+#[tracing::instrument(name = "Erase subscriber data from the preference page", skip_all)]
+pub async fn erase_from_preferences(
+    form: web::Form<TokenForm>,
+    pool: web::Data<PgPool>,
+    links: web::Data<PreferenceLinks>,
+) -> Result<HttpResponse, PreferencesError> {
+    let subscriber_id = authenticate(&links, &pool, &form.token).await?;
+    let email = get_email(&pool, subscriber_id).await?;
+    erase_subscriber(&pool, &email).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body("<p>Everything we stored about you has been erased.</p>"))
+}
+
+async fn get_email(pool: &PgPool, subscriber_id: Uuid) -> Result<String, anyhow::Error> {
+    let subscriber = sqlx::query!(
+        r#"SELECT email FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to fetch the subscriber email")?;
+    Ok(subscriber.email)
+}
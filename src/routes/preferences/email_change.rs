@@ -0,0 +1,151 @@
+use crate::configuration::SubscriptionSettings;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::preference_links::{PreferenceLinkError, PreferenceLinks};
+use crate::routes::generate_subscription_token;
+use crate::routes::preferences::{authenticate, see_preferences, PreferencesError};
+use crate::startup::ApplicationBaseUrl;
+use crate::suppression_list::can_be_mailed;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct EmailForm {
+    token: String,
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailChangeParameters {
+    email_change_token: String,
+}
+
+/// Start moving the subscription to a new address.
+/// Nothing changes until the link sent to the new address is followed.
+#[tracing::instrument(name = "Request an email change", skip_all)]
+pub async fn request_email_change(
+    form: web::Form<EmailForm>,
+    pool: web::Data<PgPool>,
+    links: web::Data<PreferenceLinks>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, PreferencesError> {
+    let subscriber_id = authenticate(&links, &pool, &form.token).await?;
+    let new_email = SubscriberEmail::parse(form.0.email)
+        .map_err(|e| PreferencesError::ValidationError(format!("The email {}", e)))?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    // Only the latest request can be confirmed
+    sqlx::query!(
+        r#"DELETE FROM email_change_requests WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to revoke previous email change requests")?;
+    let email_change_token = generate_subscription_token();
+    sqlx::query!(
+        r#"
+        INSERT INTO email_change_requests (email_change_token, subscriber_id, new_email)
+        VALUES ($1, $2, $3)
+        "#,
+        email_change_token,
+        subscriber_id,
+        new_email.as_ref()
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to store the email change request")?;
+
+    let confirmation_link = format!(
+        "{}/subscriptions/preferences/email/confirm?email_change_token={}",
+        base_url.0, email_change_token
+    );
+    if can_be_mailed(&mut transaction, new_email.as_ref())
+        .await
+        .context("Failed to check the suppression list")?
+    {
+        email_client
+            .send_mail(
+                &new_email,
+                "Confirm your new email address",
+                &format!(
+                    "Click <a href=\"{}\">here</a> to receive the newsletter at this address.",
+                    confirmation_link
+                ),
+                &format!(
+                    "Visit {} to receive the newsletter at this address.",
+                    confirmation_link
+                ),
+            )
+            .await
+            .context("Failed to send the email change confirmation")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to request an email change")?;
+    Ok(see_preferences(&form.0.token))
+}
+
+#[tracing::instrument(name = "Confirm an email change", skip_all)]
+pub async fn confirm_email_change(
+    params: web::Query<EmailChangeParameters>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, PreferencesError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let request = sqlx::query!(
+        r#"
+        DELETE FROM email_change_requests
+        WHERE email_change_token = $1
+        RETURNING subscriber_id, new_email, created_at
+        "#,
+        params.email_change_token
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to retrieve the email change request")?
+    .ok_or(PreferenceLinkError::Invalid)?;
+    if request.created_at + settings.confirmation_token_ttl() < Utc::now() {
+        transaction
+            .commit()
+            .await
+            .context("Failed to discard an expired email change request")?;
+        return Err(PreferenceLinkError::Expired.into());
+    }
+
+    let outcome = sqlx::query!(
+        r#"UPDATE subscriptions SET email = $1 WHERE id = $2"#,
+        request.new_email,
+        request.subscriber_id
+    )
+    .execute(&mut transaction)
+    .await;
+    match outcome {
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("23505") => {
+            return Err(PreferencesError::Conflict(
+                "This email address is already subscribed.".into(),
+            ));
+        }
+        outcome => {
+            outcome.context("Failed to update the subscriber email")?;
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to change an email")?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body("<p>Your email address has been updated.</p>"))
+}
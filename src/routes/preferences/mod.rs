@@ -0,0 +1,227 @@
+mod data;
+mod email_change;
+mod update;
+
+pub use data::*;
+pub use email_change::*;
+pub use update::*;
+
+use crate::configuration::SubscriptionSettings;
+use crate::preference_links::{PreferenceLinkError, PreferenceLinks};
+use crate::routes::error_chain_fmt;
+use actix_web::http::header::{ContentType, LOCATION};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::Utc;
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+use std::fmt::{Debug, Formatter};
+
+#[derive(thiserror::Error)]
+pub enum PreferencesError {
+    #[error(transparent)]
+    InvalidLink(#[from] PreferenceLinkError),
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl Debug for PreferencesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for PreferencesError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            PreferencesError::InvalidLink(PreferenceLinkError::Invalid) => StatusCode::UNAUTHORIZED,
+            PreferencesError::InvalidLink(PreferenceLinkError::Expired) => StatusCode::GONE,
+            PreferencesError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            PreferencesError::Conflict(_) => StatusCode::CONFLICT,
+            PreferencesError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            PreferencesError::UnexpectedError(_) => HttpResponse::new(self.status_code()),
+            _ => HttpResponse::build(self.status_code())
+                .content_type(ContentType::html())
+                .body(format!("<p>{}</p>", encode_text(&self.to_string()))),
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct TokenParameters {
+    token: String,
+}
+
+/// Check the signed token and that the subscriber it was issued to still exists.
+async fn authenticate(
+    links: &PreferenceLinks,
+    pool: &PgPool,
+    token: &str,
+) -> Result<Uuid, PreferencesError> {
+    let subscriber_id = links.verify(token)?;
+    let exists = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the subscriber")?
+    .is_some();
+    if !exists {
+        return Err(PreferenceLinkError::Invalid.into());
+    }
+    Ok(subscriber_id)
+}
+
+/// Send the subscriber back to their preference page once a change is saved.
+fn see_preferences(token: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((
+            LOCATION,
+            format!("/subscriptions/preferences?token={}", token),
+        ))
+        .finish()
+}
+
+#[tracing::instrument(name = "Show subscriber preferences", skip_all)]
+pub async fn preferences(
+    params: web::Query<TokenParameters>,
+    pool: web::Data<PgPool>,
+    links: web::Data<PreferenceLinks>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, PreferencesError> {
+    let subscriber_id = authenticate(&links, &pool, &params.token).await?;
+    let subscriber = sqlx::query!(
+        r#"SELECT name, email, status FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_one(pool.get_ref())
+    .await
+    .context("Failed to fetch the subscriber")?;
+    let pending_email_change = sqlx::query!(
+        r#"
+        SELECT new_email
+        FROM email_change_requests
+        WHERE subscriber_id = $1 AND created_at > $2
+        ORDER BY created_at DESC
+        LIMIT 1
+        "#,
+        subscriber_id,
+        Utc::now() - settings.confirmation_token_ttl()
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to fetch pending email changes")?
+    .map(|r| {
+        format!(
+            "<p>Check {} for a link to confirm your new address.</p>",
+            encode_text(&r.new_email)
+        )
+    })
+    .unwrap_or_default();
+
+    let token = encode_double_quoted_attribute(&params.token);
+    let (status, delivery_form) = match subscriber.status.as_str() {
+        "paused" => (
+            "Delivery of new issues is paused.",
+            format!(
+                r#"<form action="/subscriptions/preferences/resume" method="post">
+        <input type="hidden" name="token" value="{}">
+        <button type="submit">Resume delivery</button>
+    </form>"#,
+                token
+            ),
+        ),
+        "unsubscribed" => (
+            "You are unsubscribed.",
+            format!(
+                r#"<form action="/subscriptions/preferences/resume" method="post">
+        <input type="hidden" name="token" value="{}">
+        <button type="submit">Subscribe again</button>
+    </form>"#,
+                token
+            ),
+        ),
+        "confirmed" => (
+            "You are receiving new issues.",
+            format!(
+                r#"<form action="/subscriptions/preferences/pause" method="post">
+        <input type="hidden" name="token" value="{token}">
+        <button type="submit">Pause delivery</button>
+    </form>
+    <form action="/subscriptions/preferences/unsubscribe" method="post">
+        <input type="hidden" name="token" value="{token}">
+        <button type="submit">Unsubscribe</button>
+    </form>"#,
+                token = token
+            ),
+        ),
+        // Nothing the subscriber can change from here, no delivery form
+        "pending_confirmation" => (
+            "Your subscription is not confirmed yet, follow the link in the email we sent you.",
+            String::new(),
+        ),
+        "bounced" => (
+            "We stopped sending new issues because emails to this address bounced.",
+            String::new(),
+        ),
+        "complained" => (
+            "We stopped sending new issues because one of them was reported as spam.",
+            String::new(),
+        ),
+        other => {
+            return Err(anyhow::anyhow!("Unknown subscription status `{}`", other).into());
+        }
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Your subscription</title>
+</head>
+<body>
+    <h1>Your subscription</h1>
+    <p>{status}</p>
+    <form action="/subscriptions/preferences/name" method="post">
+        <input type="hidden" name="token" value="{token}">
+        <label>Name <input type="text" name="name" value="{name}"></label>
+        <button type="submit">Update name</button>
+    </form>
+    <form action="/subscriptions/preferences/email" method="post">
+        <input type="hidden" name="token" value="{token}">
+        <label>Email <input type="email" name="email" value="{email}"></label>
+        <button type="submit">Change email</button>
+    </form>
+    {pending_email_change}
+    {delivery_form}
+    <h2>Your data</h2>
+    <p><a href="/subscriptions/preferences/data?token={token}">Download everything we store about you</a></p>
+    <form action="/subscriptions/preferences/erase" method="post">
+        <input type="hidden" name="token" value="{token}">
+        <button type="submit">Erase everything we store about you</button>
+    </form>
+</body>
+</html>"#,
+            status = status,
+            token = token,
+            name = encode_double_quoted_attribute(&subscriber.name),
+            email = encode_double_quoted_attribute(&subscriber.email),
+            pending_email_change = pending_email_change,
+            delivery_form = delivery_form,
+        )))
+}
@@ -0,0 +1,135 @@
+use crate::configuration::SubscriptionSettings;
+use crate::consent_log::{record_consent, ConsentEvent, ConsentRecord};
+use crate::domain::SubscriberName;
+use crate::preference_links::PreferenceLinks;
+use crate::routes::preferences::{authenticate, see_preferences, PreferencesError};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct TokenForm {
+    pub(super) token: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct NameForm {
+    token: String,
+    name: String,
+}
+
+#[tracing::instrument(name = "Update subscriber name", skip_all)]
+pub async fn update_name(
+    form: web::Form<NameForm>,
+    pool: web::Data<PgPool>,
+    links: web::Data<PreferenceLinks>,
+) -> Result<HttpResponse, PreferencesError> {
+    let subscriber_id = authenticate(&links, &pool, &form.token).await?;
+    let name = SubscriberName::parse(form.0.name)
+        .map_err(|e| PreferencesError::ValidationError(format!("The name {}", e)))?;
+    sqlx::query!(
+        r#"UPDATE subscriptions SET name = $1 WHERE id = $2"#,
+        name.as_ref(),
+        subscriber_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to update the subscriber name")?;
+    Ok(see_preferences(&form.0.token))
+}
+
+/// Stop delivering new issues until the subscriber resumes.
+#[tracing::instrument(name = "Pause delivery", skip_all)]
+pub async fn pause_delivery(
+    form: web::Form<TokenForm>,
+    pool: web::Data<PgPool>,
+    links: web::Data<PreferenceLinks>,
+) -> Result<HttpResponse, PreferencesError> {
+    let subscriber_id = authenticate(&links, &pool, &form.token).await?;
+    sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'paused' WHERE id = $1 AND status = 'confirmed'"#,
+        subscriber_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to pause delivery")?;
+    Ok(see_preferences(&form.token))
+}
+
+/// Resume a paused subscription, or subscribe again after unsubscribing.
+/// The signed link proves the subscriber owns the address, there is nothing
+/// left to confirm: subscribing again is recorded as a confirmed consent.
+#[tracing::instrument(name = "Resume delivery", skip_all)]
+pub async fn resume_delivery(
+    form: web::Form<TokenForm>,
+    pool: web::Data<PgPool>,
+    links: web::Data<PreferenceLinks>,
+    settings: web::Data<SubscriptionSettings>,
+    request: HttpRequest,
+) -> Result<HttpResponse, PreferencesError> {
+    let subscriber_id = authenticate(&links, &pool, &form.token).await?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let status = sqlx::query!(
+        r#"SELECT status FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .context("Failed to fetch the subscriber")?
+    .status;
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'confirmed', confirmed_at = COALESCE(confirmed_at, now())
+        WHERE id = $1 AND status IN ('paused', 'unsubscribed')
+        "#,
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to resume delivery")?;
+    if status == "unsubscribed" {
+        let consent = ConsentRecord::from_request(
+            &request,
+            "preference_page".into(),
+            settings.consent_text_version.clone(),
+        );
+        record_consent(
+            &mut transaction,
+            subscriber_id,
+            ConsentEvent::Confirmed,
+            &consent,
+        )
+        .await
+        .context("Failed to record the subscriber consent")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to resume delivery")?;
+    Ok(see_preferences(&form.token))
+}
+
+#[tracing::instrument(name = "Unsubscribe from the preference page", skip_all)]
+pub async fn unsubscribe_from_preferences(
+    form: web::Form<TokenForm>,
+    pool: web::Data<PgPool>,
+    links: web::Data<PreferenceLinks>,
+) -> Result<HttpResponse, PreferencesError> {
+    let subscriber_id = authenticate(&links, &pool, &form.token).await?;
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'unsubscribed'
+        WHERE id = $1 AND status IN ('confirmed', 'paused')
+        "#,
+        subscriber_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to unsubscribe")?;
+    Ok(see_preferences(&form.token))
+}
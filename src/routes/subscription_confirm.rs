@@ -1,61 +1,267 @@
+use crate::configuration::SubscriptionSettings;
+use crate::consent_log::{
+    record_consent, signed_up_consent_text_version, ConsentEvent, ConsentRecord,
+};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::routes::{
+    error_chain_fmt, generate_subscription_token, restart_confirmation, send_confirmation_email,
+    store_token,
+};
+use crate::startup::ApplicationBaseUrl;
+use crate::suppression_list::lift_erasure;
+use actix_web::http::StatusCode;
 use actix_web::web::Query;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
 use sqlx::types::Uuid;
 use sqlx::PgPool;
+use std::fmt::{Debug, Formatter};
 
 #[derive(serde::Deserialize)]
 pub struct Parameters {
     subscription_token: String,
 }
-#[tracing::instrument(name = "Confirm a pending subscription", skip(params))]
-pub async fn confirm(params: Query<Parameters>, pool: web::Data<PgPool>) -> HttpResponse {
-    let id = match get_subscriber_from_token(&pool, &params.subscription_token).await {
-        Ok(id) => id,
-        Err(_) => return HttpResponse::InternalServerError().finish(),
-    };
 
-    match id {
-        None => HttpResponse::Unauthorized().finish(),
-        Some(subscriber_id) => {
-            if confirm_subscriber(&pool, subscriber_id).await.is_err() {
-                return HttpResponse::InternalServerError().finish();
+#[derive(thiserror::Error)]
+pub enum ConfirmationError {
+    #[error("This confirmation link is not valid.")]
+    UnknownToken,
+    #[error("This confirmation link has already been used.")]
+    TokenAlreadyUsed { resend_link: String },
+    #[error("This confirmation link has expired.")]
+    TokenExpired { resend_link: String },
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl Debug for ConfirmationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ConfirmationError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ConfirmationError::UnknownToken | ConfirmationError::TokenAlreadyUsed { .. } => {
+                StatusCode::UNAUTHORIZED
             }
-            HttpResponse::Ok().finish()
+            ConfirmationError::TokenExpired { .. } => StatusCode::GONE,
+            ConfirmationError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            ConfirmationError::UnknownToken => {
+                format!("<p>{} Subscribe again to receive a new one.</p>", self)
+            }
+            ConfirmationError::TokenAlreadyUsed { resend_link }
+            | ConfirmationError::TokenExpired { resend_link } => format!(
+                "<p>{}</p><p><a href=\"{}\">Send me a new confirmation link</a></p>",
+                self, resend_link
+            ),
+            ConfirmationError::UnexpectedError(_) => {
+                return HttpResponse::new(self.status_code());
+            }
+        };
+        HttpResponse::build(self.status_code())
+            .content_type("text/html; charset=utf-8")
+            .body(body)
+    }
 }
 
-#[tracing::instrument(name = "Get subscriber id from token", skip(pool, token))]
-pub async fn get_subscriber_from_token(
-    pool: &PgPool,
-    token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        r#"SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = $1"#,
-        token
+#[tracing::instrument(
+    name = "Confirm a pending subscription",
+    skip(params, pool, base_url, settings, request)
+)]
+pub async fn confirm(
+    params: Query<Parameters>,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+    request: HttpRequest,
+) -> Result<HttpResponse, ConfirmationError> {
+    let token = get_token(&pool, &params.subscription_token)
+        .await
+        .context("Failed to retrieve the subscription token")?
+        .ok_or(ConfirmationError::UnknownToken)?;
+    let resend_link = format!(
+        "{}/subscriptions/confirm/resend?subscription_token={}",
+        base_url.0, params.subscription_token
+    );
+    if token.consumed_at.is_some() {
+        return Err(ConfirmationError::TokenAlreadyUsed { resend_link });
+    }
+    if token.created_at + settings.confirmation_token_ttl() < Utc::now() {
+        return Err(ConfirmationError::TokenExpired { resend_link });
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let n_consumed = sqlx::query!(
+        r#"
+        UPDATE subscription_tokens
+        SET consumed_at = now()
+        WHERE subscription_token = $1 AND consumed_at IS NULL
+        "#,
+        params.subscription_token
     )
-    .fetch_optional(pool)
+    .execute(&mut transaction)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {}", e);
-        e
-    })?;
-
-    Ok(result.map(|r| r.subscriber_id))
+    .context("Failed to mark the subscription token as used")?
+    .rows_affected();
+    // Someone else confirmed with the same token since we looked it up
+    if n_consumed == 0 {
+        return Err(ConfirmationError::TokenAlreadyUsed { resend_link });
+    }
+    // Reminders add a token without revoking the previous ones, none of them
+    // must be usable once the subscription is confirmed
+    sqlx::query!(
+        r#"
+        UPDATE subscription_tokens
+        SET consumed_at = now()
+        WHERE subscriber_id = $1 AND consumed_at IS NULL
+        "#,
+        token.subscriber_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to mark the other subscription tokens as used")?;
+    let subscriber = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'confirmed', confirmed_at = now()
+        WHERE id = $1 AND status = 'pending_confirmation'
+        RETURNING email
+        "#,
+        token.subscriber_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to confirm the subscriber")?;
+    // Already confirmed, and maybe unsubscribed or paused since: leave the status alone
+    let subscriber = match subscriber {
+        Some(subscriber) => subscriber,
+        None => {
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit the SQL transaction to consume a token")?;
+            return Ok(HttpResponse::Ok()
+                .content_type("text/html; charset=utf-8")
+                .body("<p>Your subscription is already confirmed.</p>"));
+        }
+    };
+    lift_erasure(&mut transaction, &subscriber.email)
+        .await
+        .context("Failed to lift the suppression of an erased subscriber")?;
+    // Subscribers who never used the form, e.g. imported ones, agree to the current text
+    let consent_text_version =
+        signed_up_consent_text_version(&mut transaction, token.subscriber_id)
+            .await
+            .context("Failed to fetch the consent given at sign-up")?
+            .unwrap_or_else(|| settings.consent_text_version.clone());
+    let consent =
+        ConsentRecord::from_request(&request, "confirmation_link".into(), consent_text_version);
+    record_consent(
+        &mut transaction,
+        token.subscriber_id,
+        ConsentEvent::Confirmed,
+        &consent,
+    )
+    .await
+    .context("Failed to record the subscriber consent")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to confirm a subscriber")?;
+    Ok(HttpResponse::Ok().finish())
 }
 
-#[tracing::instrument(name = "Confirm subscriber", skip(pool, subscriber_id))]
-pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' where id = $1"#,
-        subscriber_id
+/// Send a fresh confirmation link to the subscriber a stale token was issued to.
+#[tracing::instrument(
+    name = "Resend a confirmation email",
+    skip(params, pool, email_client, base_url)
+)]
+pub async fn resend_confirmation(
+    params: Query<Parameters>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, ConfirmationError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT s.id, s.email, s.status
+        FROM subscription_tokens t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE t.subscription_token = $1
+        FOR UPDATE OF s
+        "#,
+        params.subscription_token
     )
-    .execute(pool)
+    .fetch_optional(&mut transaction)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {}", e);
-        e
-    })?;
+    .context("Failed to retrieve the subscriber the token was issued to")?
+    .ok_or(ConfirmationError::UnknownToken)?;
+    if subscriber.status != "pending_confirmation" {
+        return Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body("<p>There is no pending subscription to confirm.</p>"));
+    }
+    let email = SubscriberEmail::parse(subscriber.email)
+        .map_err(anyhow::Error::msg)
+        .context("The stored subscriber email is invalid")?;
 
-    Ok(())
+    restart_confirmation(&mut transaction, subscriber.id)
+        .await
+        .context("Failed to revoke the previous subscription tokens")?;
+    let subscription_token = generate_subscription_token();
+    store_token(&mut transaction, subscriber.id, &subscription_token)
+        .await
+        .context("Failed to store a new confirmation token")?;
+    send_confirmation_email(
+        &pool,
+        &email_client,
+        &email,
+        &base_url.0,
+        &subscription_token,
+    )
+    .await
+    .context("Failed to send a confirmation email")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to resend a confirmation")?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body("<p>A new confirmation link is on its way.</p>"))
+}
+
+struct SubscriptionToken {
+    subscriber_id: Uuid,
+    created_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+}
+
+#[tracing::instrument(name = "Get subscription token", skip(pool, token))]
+async fn get_token(pool: &PgPool, token: &str) -> Result<Option<SubscriptionToken>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriptionToken,
+        r#"
+        SELECT subscriber_id, created_at, consumed_at
+        FROM subscription_tokens
+        WHERE subscription_token = $1
+        "#,
+        token
+    )
+    .fetch_optional(pool)
+    .await
 }
@@ -0,0 +1,103 @@
+use crate::routes::error_chain_fmt;
+use actix_web::http::StatusCode;
+use actix_web::web::Query;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use html_escape::encode_double_quoted_attribute;
+use sqlx::PgPool;
+use std::fmt::{Debug, Formatter};
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeParameters {
+    unsubscribe_token: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum UnsubscribeError {
+    #[error("There is no subscriber associated with the provided token.")]
+    UnknownToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl Debug for UnsubscribeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for UnsubscribeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            UnsubscribeError::UnknownToken => StatusCode::UNAUTHORIZED,
+            UnsubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// The link in the email body. It only asks for confirmation: mail scanners
+/// that prefetch links must not unsubscribe anyone.
+#[tracing::instrument(name = "Show the unsubscribe form", skip(params, pool))]
+pub async fn unsubscribe_form(
+    params: Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    if !token_exists(&pool, &params.unsubscribe_token)
+        .await
+        .context("Failed to look up the unsubscribe token")?
+    {
+        return Err(UnsubscribeError::UnknownToken);
+    }
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(format!(
+            r#"<p>Do you want to stop receiving new issues?</p>
+    <form action="/subscriptions/unsubscribe?unsubscribe_token={}" method="post">
+        <button type="submit">Unsubscribe</button>
+    </form>"#,
+            encode_double_quoted_attribute(&params.unsubscribe_token)
+        )))
+}
+
+/// Handles both the form behind the link in the email body and the one-click
+/// `List-Unsubscribe-Post` request sent by mailbox providers.
+/// Bounced, complained and pending subscribers keep their status, they are not
+/// mailed new issues anyway.
+#[tracing::instrument(name = "Unsubscribe a subscriber", skip(params, pool))]
+pub async fn unsubscribe(
+    params: Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    let n_updated = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'unsubscribed'
+        WHERE unsubscribe_token = $1 AND status IN ('confirmed', 'paused')
+        "#,
+        params.unsubscribe_token
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to mark the subscriber as unsubscribed")?
+    .rows_affected();
+    if n_updated == 0
+        && !token_exists(&pool, &params.unsubscribe_token)
+            .await
+            .context("Failed to look up the unsubscribe token")?
+    {
+        return Err(UnsubscribeError::UnknownToken);
+    }
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body("<p>You have been unsubscribed and will not receive further issues.</p>"))
+}
+
+async fn token_exists(pool: &PgPool, unsubscribe_token: &str) -> Result<bool, sqlx::Error> {
+    let subscriber = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE unsubscribe_token = $1"#,
+        unsubscribe_token
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(subscriber.is_some())
+}
@@ -1,8 +1,11 @@
-use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::configuration::SubscriptionSettings;
+use crate::consent_log::{record_consent, ConsentEvent, ConsentRecord};
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName, ValidationError};
 use crate::email_client::EmailClient;
 use crate::startup::ApplicationBaseUrl;
+use crate::suppression_list::{suppression_reason, SuppressionReason};
 use actix_web::http::StatusCode;
-use actix_web::{web, HttpResponse, ResponseError};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, ResponseError};
 use anyhow::Context;
 use chrono::Utc;
 use rand::distributions::Alphanumeric;
@@ -10,25 +13,73 @@ use rand::{thread_rng, Rng};
 use sqlx::types::Uuid;
 use sqlx::{PgPool, Postgres, Transaction};
 use std::fmt::{Debug, Formatter};
-use tracing::error;
+use tracing::{field::display, Span};
 
+/// The subscription request, sent either as a form or as JSON.
+/// Missing fields are reported by the validation, along with invalid ones.
 #[derive(serde::Deserialize)]
 pub struct FormData {
+    #[serde(default)]
     email: String,
+    #[serde(default)]
     name: String,
+    /// Identifies the form the subscriber used, when there is more than one.
+    source: Option<String>,
+    /// The version of the consent text displayed by the form.
+    consent_text_version: Option<String>,
+}
+
+impl FormData {
+    /// Parse the body according to its `Content-Type`.
+    fn from_body(request: &HttpRequest, body: &[u8]) -> Result<Self, SubscriberError> {
+        match request.content_type() {
+            "application/x-www-form-urlencoded" => serde_urlencoded::from_bytes(body)
+                .map_err(|e| SubscriberError::MalformedBody(e.to_string())),
+            "application/json" => serde_json::from_slice(body)
+                .map_err(|e| SubscriberError::MalformedBody(e.to_string())),
+            content_type => Err(SubscriberError::UnsupportedContentType(
+                content_type.to_owned(),
+            )),
+        }
+    }
 }
 
 impl TryFrom<FormData> for NewSubscriber {
-    type Error = String;
+    type Error = Vec<FieldError>;
 
+    /// Every invalid field is reported, not only the first one.
     fn try_from(value: FormData) -> Result<Self, Self::Error> {
-        let name = SubscriberName::parse(value.name)?;
-        let email = SubscriberEmail::parse(value.email)?;
-        Ok(NewSubscriber { email, name })
+        let name = SubscriberName::parse(value.name).map_err(|e| FieldError::new("name", e));
+        let email = SubscriberEmail::parse(value.email).map_err(|e| FieldError::new("email", e));
+        match (email, name) {
+            (Ok(email), Ok(name)) => Ok(NewSubscriber { email, name }),
+            (email, name) => Err(email.err().into_iter().chain(name.err()).collect()),
+        }
+    }
+}
+
+/// A rejected field, e.g.
+/// `{"field": "name", "code": "too_long", "max": 256, "message": "The name must be ..."}`.
+#[derive(serde::Serialize, Debug)]
+pub struct FieldError {
+    field: &'static str,
+    #[serde(flatten)]
+    error: ValidationError,
+    message: String,
+}
+
+impl FieldError {
+    fn new(field: &'static str, error: ValidationError) -> Self {
+        let message = format!("The {} {}", field, error);
+        Self {
+            field,
+            error,
+            message,
+        }
     }
 }
 
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
@@ -38,8 +89,12 @@ fn generate_subscription_token() -> String {
 
 #[derive(thiserror::Error)]
 pub enum SubscriberError {
-    #[error("{0}")]
-    ValidationError(String),
+    #[error("{}", .0.iter().map(|e| e.message.as_str()).collect::<Vec<_>>().join(", "))]
+    ValidationError(Vec<FieldError>),
+    #[error("The request body could not be parsed: {0}")]
+    MalformedBody(String),
+    #[error("Unsupported content type `{0}`, send a form or JSON")]
+    UnsupportedContentType(String),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -53,40 +108,120 @@ impl Debug for SubscriberError {
 impl ResponseError for SubscriberError {
     fn status_code(&self) -> StatusCode {
         match self {
-            SubscriberError::ValidationError(_) => StatusCode::BAD_REQUEST,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
+            SubscriberError::ValidationError(_) | SubscriberError::MalformedBody(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            SubscriberError::UnsupportedContentType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            SubscriberError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    /// Client errors are described in JSON, e.g.
+    /// `{"errors": [{"field": "email", "message": "..."}]}`.
+    fn error_response(&self) -> HttpResponse {
+        let errors = match self {
+            SubscriberError::ValidationError(errors) => serde_json::json!(errors),
+            SubscriberError::MalformedBody(_) | SubscriberError::UnsupportedContentType(_) => {
+                serde_json::json!([{ "message": self.to_string() }])
+            }
+            SubscriberError::UnexpectedError(_) => return HttpResponse::new(self.status_code()),
+        };
+        HttpResponse::build(self.status_code()).json(serde_json::json!({ "errors": errors }))
+    }
 }
 
-impl From<String> for SubscriberError {
-    fn from(s: String) -> Self {
-        Self::ValidationError(s)
+impl From<Vec<FieldError>> for SubscriberError {
+    fn from(errors: Vec<FieldError>) -> Self {
+        Self::ValidationError(errors)
     }
 }
 
 #[tracing::instrument(
     name="Adding a new subscriber",
-    skip(form, pool, base_url),
+    skip(body, pool, base_url, settings, request),
     fields(
-        subscriber_email = %form.email,
-        subscriber_name = %form.name
+        subscriber_email = tracing::field::Empty,
+        subscriber_name = tracing::field::Empty
     )
 )]
 pub async fn subscribe(
-    form: web::Form<FormData>,
+    body: web::Bytes,
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, SubscriberError> {
-    let new_subscriber = form.0.try_into()?;
+    let form = FormData::from_body(&request, &body)?;
+    Span::current()
+        .record("subscriber_email", &display(&form.email))
+        .record("subscriber_name", &display(&form.name));
+    let consent = ConsentRecord::from_request(
+        &request,
+        form.source
+            .clone()
+            .unwrap_or_else(|| "subscription_form".into()),
+        form.consent_text_version
+            .clone()
+            .unwrap_or_else(|| settings.consent_text_version.clone()),
+    );
+    let new_subscriber: NewSubscriber = form.try_into()?;
+    match suppression_reason(pool.as_ref(), new_subscriber.email.as_ref())
+        .await
+        .context("Failed to check the suppression list")?
+    {
+        // The erasure is only forgotten once the owner of the address confirms,
+        // anyone can fill in the form
+        Some(SuppressionReason::Erased) => {}
+        // Answer as usual, the caller must not be able to tell the address is suppressed
+        Some(reason) => {
+            tracing::warn!(?reason, "Ignoring a subscription from a suppressed address");
+            return Ok(HttpResponse::Ok().finish());
+        }
+        None => {}
+    }
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire Postgres connection from the pool")?;
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    let subscriber_id = match insert_subscriber(&mut transaction, &new_subscriber)
         .await
-        .context("Failed to insert a new subscriber in the DB")?;
+        .context("Failed to insert a new subscriber in the DB")?
+    {
+        Some(subscriber_id) => subscriber_id,
+        None => {
+            let existing = get_existing_subscriber(&mut transaction, &new_subscriber.email)
+                .await
+                .context("Failed to retrieve the existing subscriber")?;
+            // Whatever the status, answer exactly as we do for new subscribers: the
+            // caller must not be able to tell who is on the list.
+            match existing.status.as_str() {
+                // A pending subscription, e.g. one whose token expired, gets a new
+                // link. So do subscribers who left and want to come back: nothing
+                // changes until they follow it.
+                "pending_confirmation" | "unsubscribed" => {
+                    restart_confirmation(&mut transaction, existing.id)
+                        .await
+                        .context("Failed to restart the subscription confirmation")?;
+                }
+                // Confirmed and paused subscribers are already on the list, bounced
+                // and complained addresses are treated like suppressed ones
+                status => {
+                    tracing::info!(status, "Ignoring a subscription for an existing subscriber");
+                    return Ok(HttpResponse::Ok().finish());
+                }
+            }
+            existing.id
+        }
+    };
+    record_consent(
+        &mut transaction,
+        subscriber_id,
+        ConsentEvent::Subscribed,
+        &consent,
+    )
+    .await
+    .context("Failed to record the subscriber consent")?;
 
     let subscription_token = generate_subscription_token();
 
@@ -94,8 +229,9 @@ pub async fn subscribe(
         .await
         .context("Failed to store a confirmation token for the new subscriber")?;
     send_confirmation_email(
+        &pool,
         &email_client,
-        new_subscriber,
+        &new_subscriber.email,
         &base_url.0,
         &subscription_token,
     )
@@ -110,21 +246,34 @@ pub async fn subscribe(
 
 #[tracing::instrument(
     name = "Sending confirmation email to new subscriber",
-    skip(email_client, new_subscriber, subscription_token)
+    skip(pool, email_client, recipient, subscription_token)
 )]
 pub async fn send_confirmation_email(
+    pool: &PgPool,
     email_client: &EmailClient,
-    new_subscriber: NewSubscriber,
+    recipient: &SubscriberEmail,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), anyhow::Error> {
+    match suppression_reason(pool, recipient.as_ref()).await? {
+        // Subscribers who asked to be erased can sign up again, the suppression
+        // is lifted when they confirm
+        None | Some(SuppressionReason::Erased) => {}
+        Some(reason) => {
+            tracing::warn!(
+                ?reason,
+                "Not sending a confirmation email to a suppressed address"
+            );
+            return Ok(());
+        }
+    }
     let confirmation_link = format!(
         "{}/subscriptions/confirm?subscription_token={}",
         base_url, subscription_token
     );
     email_client
         .send_mail(
-            &new_subscriber.email,
+            recipient,
             "Welcome!",
             &format!(
                 "Welcome!<br />\
@@ -137,9 +286,11 @@ pub async fn send_confirmation_email(
                 confirmation_link
             ),
         )
-        .await
+        .await?;
+    Ok(())
 }
 
+/// Returns `None` if someone already subscribed with this email.
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
     skip(new_subscriber, transaction)
@@ -147,21 +298,65 @@ pub async fn send_confirmation_email(
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
+) -> Result<Option<Uuid>, sqlx::Error> {
     let subscriber_id = Uuid::new_v4();
-    sqlx::query!(
+    let result = sqlx::query!(
         r#"
-    INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-    VALUES ($1, $2, $3, $4, 'pending_confirmation')
+    INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+    VALUES ($1, $2, $3, $4, 'pending_confirmation', $5)
+    ON CONFLICT (email) DO NOTHING
+    RETURNING id
             "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
-        Utc::now()
+        Utc::now(),
+        generate_subscription_token()
+    )
+    .fetch_optional(transaction)
+    .await?;
+    Ok(result.map(|r| r.id))
+}
+
+struct ExistingSubscriber {
+    id: Uuid,
+    status: String,
+}
+
+#[tracing::instrument(name = "Getting an existing subscriber", skip(transaction, email))]
+async fn get_existing_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<ExistingSubscriber, sqlx::Error> {
+    sqlx::query_as!(
+        ExistingSubscriber,
+        r#"SELECT id, status FROM subscriptions WHERE email = $1 FOR UPDATE"#,
+        email.as_ref()
+    )
+    .fetch_one(transaction)
+    .await
+}
+
+/// Put a subscriber in the `pending_confirmation` state, if they are not already,
+/// and revoke the tokens sent to them so far: a fresh one is about to be issued.
+#[tracing::instrument(name = "Restarting a subscription confirmation", skip(transaction))]
+pub async fn restart_confirmation(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'pending_confirmation' WHERE id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
     )
     .execute(transaction)
     .await?;
-    Ok(subscriber_id)
+    Ok(())
 }
 
 #[tracing::instrument(
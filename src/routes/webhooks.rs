@@ -0,0 +1,156 @@
+use crate::authentication::basic_authentication;
+use crate::configuration::WebhookSettings;
+use crate::routes::error_chain_fmt;
+use crate::suppression_list::{suppress, SuppressionReason};
+use actix_web::http::header::{HeaderValue, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::{Debug, Formatter};
+use subtle::ConstantTimeEq;
+
+/// The subset of Postmark's webhook payloads we act upon.
+/// See https://postmarkapp.com/developer/webhooks/webhooks-overview
+#[derive(serde::Deserialize)]
+#[serde(tag = "RecordType")]
+pub enum PostmarkEvent {
+    Bounce(BouncedEmail),
+    SpamComplaint(ComplainedEmail),
+    /// Deliveries, opens, clicks... Postmark only sends them if they are enabled.
+    #[serde(other)]
+    Other,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BouncedEmail {
+    #[serde(rename = "Type")]
+    bounce_type: String,
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ComplainedEmail {
+    email: String,
+}
+
+impl BouncedEmail {
+    /// Soft bounces (full mailbox, auto-responders...) are worth trying again.
+    fn is_permanent(&self) -> bool {
+        matches!(self.bounce_type.as_str(), "HardBounce" | "BadEmailAddress")
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum WebhookError {
+    #[error("Authentication failed")]
+    AuthError(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl Debug for WebhookError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for WebhookError {
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            WebhookError::UnexpectedError(_) => {
+                HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            WebhookError::AuthError(_) => {
+                let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
+                let header_value = HeaderValue::from_str(r#"Basic realm="webhooks""#).unwrap();
+                response
+                    .headers_mut()
+                    .insert(WWW_AUTHENTICATE, header_value);
+                response
+            }
+        }
+    }
+}
+
+/// Stop mailing addresses that bounced for good or reported us as spam.
+/// They are also put on the suppression list, even if they are not subscribed.
+#[tracing::instrument(name = "Handle a Postmark webhook", skip_all)]
+pub async fn postmark_webhook(
+    event: web::Json<PostmarkEvent>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<WebhookSettings>,
+    request: HttpRequest,
+) -> Result<HttpResponse, WebhookError> {
+    authenticate(&request, &settings).await?;
+    match event.0 {
+        PostmarkEvent::Bounce(bounce) if bounce.is_permanent() => {
+            // A complaint is the stronger signal, it is not overwritten
+            let n_updated = sqlx::query!(
+                r#"
+                UPDATE subscriptions
+                SET status = 'bounced'
+                WHERE email = $1 AND status <> 'complained'
+                "#,
+                bounce.email
+            )
+            .execute(pool.get_ref())
+            .await
+            .context("Failed to mark the subscriber as bounced")?
+            .rows_affected();
+            suppress(pool.get_ref(), &bounce.email, SuppressionReason::Bounce)
+                .await
+                .context("Failed to suppress a bounced address")?;
+            tracing::info!(n_updated, "Recorded a hard bounce");
+        }
+        PostmarkEvent::Bounce(bounce) => {
+            tracing::info!(bounce_type = %bounce.bounce_type, "Ignoring a transient bounce");
+        }
+        PostmarkEvent::SpamComplaint(complaint) => {
+            let n_updated = sqlx::query!(
+                r#"UPDATE subscriptions SET status = 'complained' WHERE email = $1"#,
+                complaint.email
+            )
+            .execute(pool.get_ref())
+            .await
+            .context("Failed to mark the subscriber as complained")?
+            .rows_affected();
+            suppress(
+                pool.get_ref(),
+                &complaint.email,
+                SuppressionReason::Complaint,
+            )
+            .await
+            .context("Failed to suppress a complaining address")?;
+            tracing::info!(n_updated, "Recorded a spam complaint");
+        }
+        PostmarkEvent::Other => {}
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn authenticate(
+    request: &HttpRequest,
+    settings: &WebhookSettings,
+) -> Result<(), WebhookError> {
+    let credentials = basic_authentication(request.headers())
+        .await
+        .map_err(WebhookError::AuthError)?;
+    let username_matches = credentials
+        .username
+        .as_bytes()
+        .ct_eq(settings.username.as_bytes());
+    let password_matches = credentials
+        .password
+        .as_bytes()
+        .ct_eq(settings.password.as_bytes());
+    if bool::from(username_matches & password_matches) {
+        Ok(())
+    } else {
+        Err(WebhookError::AuthError(anyhow::anyhow!(
+            "Invalid webhook credentials"
+        )))
+    }
+}
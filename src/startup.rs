@@ -1,6 +1,21 @@
-use crate::configuration::{DatabaseSettings, Settings};
+use crate::configuration::{DatabaseSettings, Settings, SubscriptionSettings, WebhookSettings};
 use crate::email_client::EmailClient;
-use crate::routes::{confirm, health_check, publish_newsletter, subscribe};
+use crate::issue_delivery_worker::{run_worker_until_stopped, RetryPolicy};
+use crate::issue_scheduler::run_scheduler_until_stopped;
+use crate::preference_links::PreferenceLinks;
+use crate::routes::{
+    add_suppression, archive, archived_issue, cancel_scheduled_issue, confirm,
+    confirm_email_change, create_draft, delete_draft, download_subscriber_data,
+    erase_from_preferences, erase_subscriber_data, export_subscribers, get_draft, get_issue_status,
+    get_subscriber_data, health_check, import_subscribers, list_dead_letters, list_drafts,
+    list_suppressions, pause_delivery, postmark_webhook, preferences, publish_draft,
+    publish_newsletter, remove_suppression, request_email_change, requeue_dead_letters,
+    reschedule_issue, resend_confirmation, resume_delivery, subscribe, unsubscribe,
+    unsubscribe_form, unsubscribe_from_preferences, update_draft, update_name,
+};
+use crate::subscription_maintenance::{
+    run_confirmation_worker_until_stopped, run_maintenance_until_stopped,
+};
 use actix_web::{dev::Server, web, App, HttpServer};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
@@ -10,6 +25,12 @@ use tracing_actix_web::TracingLogger;
 pub struct Application {
     port: u16,
     server: Server,
+    connection_pool: PgPool,
+    email_client: EmailClient,
+    retry_policy: RetryPolicy,
+    base_url: String,
+    subscription_settings: SubscriptionSettings,
+    preference_links: PreferenceLinks,
 }
 
 pub struct ApplicationBaseUrl(pub String);
@@ -17,20 +38,12 @@ pub struct ApplicationBaseUrl(pub String);
 impl Application {
     pub async fn build(configuration: &Settings) -> Result<Self, std::io::Error> {
         // Build database connection pool
-        let connection = get_connection_pool(&configuration.database);
+        let connection_pool = get_connection_pool(&configuration.database);
 
         // Build email client
-        let sender_email = configuration
-            .email_client
-            .sender()
-            .expect("Invalid sender email address");
-        let timeout = configuration.email_client.timeout();
-        let email_client = EmailClient::new(
-            configuration.email_client.base_url.clone(),
-            sender_email,
-            configuration.email_client.authorization_token.clone(),
-            timeout,
-        );
+        let email_client = configuration.email_client.clone().client();
+        let preference_links = configuration.preference_links();
+
         let address = format!(
             "{}:{}",
             configuration.application.host, configuration.application.port
@@ -39,19 +52,76 @@ impl Application {
         let port = listener.local_addr().unwrap().port();
         let server = run(
             listener,
-            connection,
-            email_client,
+            connection_pool.clone(),
+            email_client.clone(),
             &configuration.application.base_url,
+            configuration.subscriptions.clone(),
+            preference_links.clone(),
+            configuration.webhooks.clone(),
         )?;
-        Ok(Self { port, server })
+        Ok(Self {
+            port,
+            server,
+            connection_pool,
+            email_client,
+            retry_policy: configuration.issue_delivery.retry_policy(),
+            base_url: configuration.application.base_url.clone(),
+            subscription_settings: configuration.subscriptions.clone(),
+            preference_links,
+        })
     }
 
     pub fn port(&self) -> u16 {
         self.port
     }
 
-    pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
-        self.server.await
+    /// Run the HTTP server, the newsletter delivery worker, the confirmation email worker,
+    /// the issue scheduler and the subscription maintenance job side by side, returning
+    /// as soon as any of them exits.
+    pub async fn run_until_stopped(self) -> Result<(), anyhow::Error> {
+        let scheduler = run_scheduler_until_stopped(self.connection_pool.clone());
+        let maintenance = run_maintenance_until_stopped(
+            self.connection_pool.clone(),
+            self.email_client.clone(),
+            self.base_url.clone(),
+            self.subscription_settings,
+        );
+        let confirmation_worker = run_confirmation_worker_until_stopped(
+            self.connection_pool.clone(),
+            self.email_client.clone(),
+            self.base_url.clone(),
+        );
+        let worker = run_worker_until_stopped(
+            self.connection_pool,
+            self.email_client,
+            self.retry_policy,
+            self.base_url,
+            self.preference_links,
+        );
+        tokio::select! {
+            outcome = self.server => report_exit("API", outcome.map_err(Into::into)),
+            outcome = worker => report_exit("Background worker", outcome),
+            outcome = confirmation_worker => report_exit("Confirmation email worker", outcome),
+            outcome = scheduler => report_exit("Issue scheduler", outcome),
+            outcome = maintenance => report_exit("Subscription maintenance", outcome),
+        }
+        Ok(())
+    }
+}
+
+fn report_exit(task_name: &str, outcome: Result<(), anyhow::Error>) {
+    match outcome {
+        Ok(()) => {
+            tracing::info!("{} has exited", task_name)
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "{} failed",
+                task_name
+            )
+        }
     }
 }
 
@@ -66,20 +136,134 @@ pub fn run(
     connection: PgPool,
     email_client: EmailClient,
     base_url: &str,
+    subscription_settings: SubscriptionSettings,
+    preference_links: PreferenceLinks,
+    webhook_settings: WebhookSettings,
 ) -> Result<Server, std::io::Error> {
     let connection_pool = web::Data::new(connection);
     let email_client = web::Data::new(email_client);
     let base_url = web::Data::new(ApplicationBaseUrl(base_url.to_string()));
+    let subscription_settings = web::Data::new(subscription_settings);
+    let preference_links = web::Data::new(preference_links);
+    let webhook_settings = web::Data::new(webhook_settings);
     let server = HttpServer::new(move || {
         App::new()
             .wrap(TracingLogger::default())
             .route("/health_check", web::get().to(health_check))
             .route("/subscriptions", web::post().to(subscribe))
             .route("/subscriptions/confirm", web::get().to(confirm))
+            .route(
+                "/subscriptions/confirm/resend",
+                web::get().to(resend_confirmation),
+            )
+            .route(
+                "/subscriptions/unsubscribe",
+                web::get().to(unsubscribe_form),
+            )
+            .route("/subscriptions/unsubscribe", web::post().to(unsubscribe))
+            .route("/subscriptions/preferences", web::get().to(preferences))
+            .route(
+                "/subscriptions/preferences/name",
+                web::post().to(update_name),
+            )
+            .route(
+                "/subscriptions/preferences/email",
+                web::post().to(request_email_change),
+            )
+            .route(
+                "/subscriptions/preferences/email/confirm",
+                web::get().to(confirm_email_change),
+            )
+            .route(
+                "/subscriptions/preferences/pause",
+                web::post().to(pause_delivery),
+            )
+            .route(
+                "/subscriptions/preferences/resume",
+                web::post().to(resume_delivery),
+            )
+            .route(
+                "/subscriptions/preferences/unsubscribe",
+                web::post().to(unsubscribe_from_preferences),
+            )
+            .route(
+                "/subscriptions/preferences/data",
+                web::get().to(download_subscriber_data),
+            )
+            .route(
+                "/subscriptions/preferences/erase",
+                web::post().to(erase_from_preferences),
+            )
             .route("/newsletters", web::post().to(publish_newsletter))
+            // Registered before `/newsletters/{newsletter_issue_id}`, which would match them too
+            .route("/newsletters/drafts", web::post().to(create_draft))
+            .route("/newsletters/drafts", web::get().to(list_drafts))
+            .route(
+                "/newsletters/drafts/{newsletter_issue_id}",
+                web::get().to(get_draft),
+            )
+            .route(
+                "/newsletters/drafts/{newsletter_issue_id}",
+                web::put().to(update_draft),
+            )
+            .route(
+                "/newsletters/drafts/{newsletter_issue_id}",
+                web::delete().to(delete_draft),
+            )
+            .route(
+                "/newsletters/drafts/{newsletter_issue_id}/publish",
+                web::post().to(publish_draft),
+            )
+            .route(
+                "/newsletters/{newsletter_issue_id}",
+                web::get().to(get_issue_status),
+            )
+            .route(
+                "/newsletters/{newsletter_issue_id}/cancel",
+                web::post().to(cancel_scheduled_issue),
+            )
+            .route(
+                "/newsletters/{newsletter_issue_id}/schedule",
+                web::put().to(reschedule_issue),
+            )
+            .route("/webhooks/postmark", web::post().to(postmark_webhook))
+            .route("/archive", web::get().to(archive))
+            .route("/archive/{slug}", web::get().to(archived_issue))
+            .route("/admin/dead_letters", web::get().to(list_dead_letters))
+            .route(
+                "/admin/dead_letters/requeue",
+                web::post().to(requeue_dead_letters),
+            )
+            .route(
+                "/admin/subscribers/export",
+                web::get().to(export_subscribers),
+            )
+            .route("/admin/suppressions", web::get().to(list_suppressions))
+            .route("/admin/suppressions", web::post().to(add_suppression))
+            .route(
+                "/admin/suppressions/remove",
+                web::post().to(remove_suppression),
+            )
+            .route(
+                "/admin/subscribers/data",
+                web::get().to(get_subscriber_data),
+            )
+            .route(
+                "/admin/subscribers/erase",
+                web::post().to(erase_subscriber_data),
+            )
+            .service(
+                web::resource("/admin/subscribers/import")
+                    // Large enough for lists with tens of thousands of subscribers
+                    .app_data(web::PayloadConfig::new(10 * 1024 * 1024))
+                    .route(web::post().to(import_subscribers)),
+            )
             .app_data(connection_pool.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
+            .app_data(subscription_settings.clone())
+            .app_data(preference_links.clone())
+            .app_data(webhook_settings.clone())
     })
     .listen(listener)?
     .run();
@@ -0,0 +1,214 @@
+use crate::consent_log::{get_consent_log, ConsentEntry};
+use crate::suppression_list::{suppress, SuppressionReason};
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+/// Everything stored about a single subscriber, as returned to data access requests.
+#[derive(serde::Serialize)]
+pub struct SubscriberData {
+    subscription: Subscription,
+    consents: Vec<ConsentEntry>,
+    confirmation_tokens: Vec<ConfirmationToken>,
+    email_change_requests: Vec<EmailChangeRequest>,
+    deliveries: Vec<Delivery>,
+    queued_deliveries: Vec<QueuedDelivery>,
+    failed_deliveries: Vec<FailedDelivery>,
+}
+
+#[derive(serde::Serialize)]
+struct Subscription {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    confirmed_at: Option<DateTime<Utc>>,
+    consent_source: Option<String>,
+    reminder_sent_at: Option<DateTime<Utc>>,
+    unsubscribe_token: String,
+}
+
+#[derive(serde::Serialize)]
+struct ConfirmationToken {
+    subscription_token: String,
+    created_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize)]
+struct EmailChangeRequest {
+    new_email: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct Delivery {
+    newsletter_issue_id: Uuid,
+    outcome: String,
+    recorded_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct QueuedDelivery {
+    newsletter_issue_id: Uuid,
+    n_attempts: i16,
+    last_error: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct FailedDelivery {
+    newsletter_issue_id: Uuid,
+    n_attempts: i16,
+    last_error: String,
+    failed_at: DateTime<Utc>,
+}
+
+/// Collect everything stored about `email`, `None` if they are not subscribed.
+#[tracing::instrument(skip(pool, email), err)]
+pub async fn collect_subscriber_data(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<SubscriberData>, anyhow::Error> {
+    let subscription = sqlx::query_as!(
+        Subscription,
+        r#"
+        SELECT id, email, name, status, subscribed_at, confirmed_at, consent_source,
+            reminder_sent_at, unsubscribe_token
+        FROM subscriptions
+        WHERE email = $1
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+    let subscription = match subscription {
+        Some(subscription) => subscription,
+        None => return Ok(None),
+    };
+    let consents = get_consent_log(pool, subscription.id).await?;
+    let confirmation_tokens = sqlx::query_as!(
+        ConfirmationToken,
+        r#"
+        SELECT subscription_token, created_at, consumed_at
+        FROM subscription_tokens
+        WHERE subscriber_id = $1
+        ORDER BY created_at
+        "#,
+        subscription.id
+    )
+    .fetch_all(pool)
+    .await?;
+    let email_change_requests = sqlx::query_as!(
+        EmailChangeRequest,
+        r#"
+        SELECT new_email, created_at
+        FROM email_change_requests
+        WHERE subscriber_id = $1
+        ORDER BY created_at
+        "#,
+        subscription.id
+    )
+    .fetch_all(pool)
+    .await?;
+    let deliveries = sqlx::query_as!(
+        Delivery,
+        r#"
+        SELECT newsletter_issue_id, outcome, recorded_at
+        FROM issue_delivery_log
+        WHERE subscriber_email = $1
+        ORDER BY recorded_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await?;
+    let queued_deliveries = sqlx::query_as!(
+        QueuedDelivery,
+        r#"
+        SELECT newsletter_issue_id, n_attempts, last_error
+        FROM issue_delivery_queue
+        WHERE subscriber_email = $1
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await?;
+    let failed_deliveries = sqlx::query_as!(
+        FailedDelivery,
+        r#"
+        SELECT newsletter_issue_id, n_attempts, last_error, failed_at
+        FROM issue_delivery_dead_letters
+        WHERE subscriber_email = $1
+        ORDER BY failed_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(Some(SubscriberData {
+        subscription,
+        consents,
+        confirmation_tokens,
+        email_change_requests,
+        deliveries,
+        queued_deliveries,
+        failed_deliveries,
+    }))
+}
+
+/// Remove everything stored about `email` and put a hash of the address on the
+/// suppression list, so that it is neither imported nor mailed again.
+/// The delivery log is anonymised rather than deleted, issue statistics stay right.
+/// Returns `false` if there was no such subscriber.
+#[tracing::instrument(skip(pool, email), err)]
+pub async fn erase_subscriber(pool: &PgPool, email: &str) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let subscriber = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE email = $1 FOR UPDATE"#,
+        email
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+    let subscriber_id = match subscriber {
+        Some(subscriber) => subscriber.id,
+        None => return Ok(false),
+    };
+
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await?;
+    // Email change requests and the consent log go with the subscription, `ON DELETE CASCADE`
+    sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, subscriber_id)
+        .execute(&mut transaction)
+        .await?;
+    sqlx::query!(
+        r#"DELETE FROM issue_delivery_queue WHERE subscriber_email = $1"#,
+        email
+    )
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!(
+        r#"DELETE FROM issue_delivery_dead_letters WHERE subscriber_email = $1"#,
+        email
+    )
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_log
+        SET subscriber_email = 'erased-' || md5(random()::text)
+        WHERE subscriber_email = $1
+        "#,
+        email
+    )
+    .execute(&mut transaction)
+    .await?;
+    suppress(&mut transaction, email, SuppressionReason::Erased).await?;
+    transaction.commit().await?;
+    tracing::info!(%subscriber_id, "Erased a subscriber");
+    Ok(true)
+}
@@ -0,0 +1,263 @@
+use crate::configuration::SubscriptionSettings;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::routes::{generate_subscription_token, send_confirmation_email, store_token};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::{field::display, Span};
+
+pub enum ReminderOutcome {
+    ReminderSent,
+    /// The reminder could not be sent, it is not retried.
+    ReminderFailed,
+    NothingDue,
+}
+
+/// Delete confirmation tokens that expired more than the retention period ago.
+/// Until then, following an expired link still gets a useful answer.
+#[tracing::instrument(skip_all, err)]
+pub async fn delete_expired_tokens(
+    pool: &PgPool,
+    settings: &SubscriptionSettings,
+) -> Result<u64, anyhow::Error> {
+    let cutoff =
+        Utc::now() - settings.confirmation_token_ttl() - settings.expired_token_retention();
+    let n_deleted = sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE created_at < $1"#,
+        cutoff
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    tracing::info!(n_deleted, "Deleted expired subscription tokens");
+    Ok(n_deleted)
+}
+
+/// Send a single reminder, with a fresh confirmation link, to the next subscriber
+/// who has not confirmed within the reminder delay.
+/// Each subscriber gets a single attempt, so one undeliverable address cannot hold
+/// up the reminders due after it.
+#[tracing::instrument(skip_all, fields(subscriber_id=tracing::field::Empty), err)]
+pub async fn try_send_confirmation_reminder(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    settings: &SubscriptionSettings,
+) -> Result<ReminderOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT id, email
+        FROM subscriptions
+        WHERE status = 'pending_confirmation'
+            AND reminder_sent_at IS NULL
+            AND subscribed_at < $1
+        ORDER BY subscribed_at
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+        Utc::now() - settings.confirmation_reminder_delay()
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+    let subscriber = match subscriber {
+        Some(subscriber) => subscriber,
+        None => return Ok(ReminderOutcome::NothingDue),
+    };
+    Span::current().record("subscriber_id", &display(subscriber.id));
+
+    sqlx::query!(
+        r#"UPDATE subscriptions SET reminder_sent_at = now() WHERE id = $1"#,
+        subscriber.id
+    )
+    .execute(&mut transaction)
+    .await?;
+    let outcome = match SubscriberEmail::parse(subscriber.email) {
+        Ok(email) => {
+            let subscription_token = generate_subscription_token();
+            store_token(&mut transaction, subscriber.id, &subscription_token)
+                .await
+                .context("Failed to store a confirmation token for the reminder")?;
+            match send_confirmation_email(pool, email_client, &email, base_url, &subscription_token)
+                .await
+            {
+                Ok(()) => {
+                    tracing::info!("Sent a confirmation reminder");
+                    ReminderOutcome::ReminderSent
+                }
+                Err(e) => {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to send a confirmation reminder. It will not be retried",
+                    );
+                    ReminderOutcome::ReminderFailed
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!(
+                error.message = %e,
+                "Skipping a pending subscriber. Their stored contact details are invalid",
+            );
+            ReminderOutcome::ReminderSent
+        }
+    };
+    // Commit even if sending failed, to record the attempt
+    transaction.commit().await?;
+    Ok(outcome)
+}
+
+/// Send the next queued confirmation email, with a fresh confirmation link.
+/// Subscribers who are no longer pending by then are skipped.
+/// A failed email is not retried here, the subscriber is reminded later on.
+#[tracing::instrument(skip_all, fields(subscriber_id=tracing::field::Empty), err)]
+pub async fn try_send_queued_confirmation(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT s.id, s.email, s.status
+        FROM confirmation_email_queue q
+        JOIN subscriptions s ON s.id = q.subscriber_id
+        ORDER BY q.enqueued_at
+        FOR UPDATE OF q
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+    let subscriber = match subscriber {
+        Some(subscriber) => subscriber,
+        None => return Ok(ExecutionOutcome::EmptyQueue),
+    };
+    Span::current().record("subscriber_id", &display(subscriber.id));
+
+    sqlx::query!(
+        r#"DELETE FROM confirmation_email_queue WHERE subscriber_id = $1"#,
+        subscriber.id
+    )
+    .execute(&mut transaction)
+    .await?;
+    if subscriber.status != "pending_confirmation" {
+        tracing::info!("Skipping a queued confirmation email. The subscriber is no longer pending");
+        transaction.commit().await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+    match SubscriberEmail::parse(subscriber.email) {
+        Ok(email) => {
+            let subscription_token = generate_subscription_token();
+            store_token(&mut transaction, subscriber.id, &subscription_token)
+                .await
+                .context("Failed to store a confirmation token")?;
+            if let Err(e) =
+                send_confirmation_email(pool, email_client, &email, base_url, &subscription_token)
+                    .await
+            {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to send a queued confirmation email. The subscriber will be reminded",
+                );
+            }
+        }
+        Err(e) => {
+            tracing::error!(
+                error.message = %e,
+                "Skipping a queued confirmation email. The stored contact details are invalid",
+            );
+        }
+    }
+    // Commit even if sending failed, the email is not retried
+    transaction.commit().await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+pub async fn run_confirmation_worker_until_stopped(
+    pool: PgPool,
+    email_client: EmailClient,
+    base_url: String,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_send_queued_confirmation(&pool, &email_client, &base_url).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+/// Delete subscribers who never confirmed within the purge delay, together with
+/// the confirmation tokens issued to them.
+#[tracing::instrument(skip_all, err)]
+pub async fn purge_unconfirmed_subscribers(
+    pool: &PgPool,
+    settings: &SubscriptionSettings,
+) -> Result<u64, anyhow::Error> {
+    let cutoff = Utc::now() - settings.unconfirmed_purge_delay();
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM subscription_tokens
+        WHERE subscriber_id IN (
+            SELECT id FROM subscriptions
+            WHERE status = 'pending_confirmation' AND subscribed_at < $1
+        )
+        "#,
+        cutoff
+    )
+    .execute(&mut transaction)
+    .await?;
+    let n_purged = sqlx::query!(
+        r#"
+        DELETE FROM subscriptions
+        WHERE status = 'pending_confirmation' AND subscribed_at < $1
+        "#,
+        cutoff
+    )
+    .execute(&mut transaction)
+    .await?
+    .rows_affected();
+    transaction.commit().await?;
+    tracing::info!(n_purged, "Purged unconfirmed subscribers");
+    Ok(n_purged)
+}
+
+/// Run every maintenance task once, reminding all subscribers who are due.
+pub async fn run_maintenance_tasks(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    settings: &SubscriptionSettings,
+) {
+    // Failures are logged by the tasks themselves, they are retried on the next run
+    let _ = delete_expired_tokens(pool, settings).await;
+    while let Ok(ReminderOutcome::ReminderSent | ReminderOutcome::ReminderFailed) =
+        try_send_confirmation_reminder(pool, email_client, base_url, settings).await
+    {}
+    let _ = purge_unconfirmed_subscribers(pool, settings).await;
+}
+
+pub async fn run_maintenance_until_stopped(
+    pool: PgPool,
+    email_client: EmailClient,
+    base_url: String,
+    settings: SubscriptionSettings,
+) -> Result<(), anyhow::Error> {
+    loop {
+        run_maintenance_tasks(&pool, &email_client, &base_url, &settings).await;
+        tokio::time::sleep(Duration::from_secs(60 * 60)).await;
+    }
+}
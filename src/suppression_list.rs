@@ -0,0 +1,177 @@
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgExecutor;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SuppressionReason {
+    /// The address bounced for good.
+    Bounce,
+    /// The recipient reported one of our emails as spam.
+    Complaint,
+    /// Added by an admin.
+    Manual,
+    /// The subscriber asked for their data to be erased, only a hash of the address is kept.
+    Erased,
+}
+
+impl SuppressionReason {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SuppressionReason::Bounce => "bounce",
+            SuppressionReason::Complaint => "complaint",
+            SuppressionReason::Manual => "manual",
+            SuppressionReason::Erased => "erased",
+        }
+    }
+
+    fn parse(s: &str) -> Result<Self, anyhow::Error> {
+        match s {
+            "bounce" => Ok(SuppressionReason::Bounce),
+            "complaint" => Ok(SuppressionReason::Complaint),
+            "manual" => Ok(SuppressionReason::Manual),
+            "erased" => Ok(SuppressionReason::Erased),
+            other => Err(anyhow::anyhow!("Unknown suppression reason `{}`", other)),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct SuppressedEmail {
+    /// `None` for erased subscribers.
+    email: Option<String>,
+    reason: SuppressionReason,
+    suppressed_at: DateTime<Utc>,
+}
+
+/// Add `email` to the suppression list.
+/// An address that is already suppressed keeps its original reason, which is
+/// never weakened to `erased`: erasing it only forgets the email.
+#[tracing::instrument(skip(executor, email))]
+pub async fn suppress(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+    reason: SuppressionReason,
+) -> Result<(), sqlx::Error> {
+    let stored_email = match reason {
+        SuppressionReason::Erased => None,
+        _ => Some(email),
+    };
+    sqlx::query!(
+        r#"
+        INSERT INTO suppressed_emails (email_hash, email, reason, suppressed_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (email_hash) DO UPDATE
+        SET email = NULL
+        WHERE EXCLUDED.reason = 'erased'
+        "#,
+        email_hash(email),
+        stored_email,
+        reason.as_str()
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Returns `false` if `email` was not on the list.
+#[tracing::instrument(skip(executor, email))]
+pub async fn unsuppress(executor: impl PgExecutor<'_>, email: &str) -> Result<bool, sqlx::Error> {
+    let n_deleted = sqlx::query!(
+        r#"DELETE FROM suppressed_emails WHERE email_hash = $1"#,
+        email_hash(email)
+    )
+    .execute(executor)
+    .await?
+    .rows_affected();
+    Ok(n_deleted > 0)
+}
+
+/// Forget that `email` was erased, once its owner confirmed a new subscription.
+/// Other suppressions stay in place.
+#[tracing::instrument(skip(executor, email))]
+pub async fn lift_erasure(executor: impl PgExecutor<'_>, email: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM suppressed_emails WHERE email_hash = $1 AND reason = 'erased'"#,
+        email_hash(email)
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Why `email` must not be mailed, `None` if it can be.
+pub async fn suppression_reason(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+) -> Result<Option<SuppressionReason>, anyhow::Error> {
+    let entry = sqlx::query!(
+        r#"SELECT reason FROM suppressed_emails WHERE email_hash = $1"#,
+        email_hash(email)
+    )
+    .fetch_optional(executor)
+    .await?;
+    entry
+        .map(|e| SuppressionReason::parse(&e.reason))
+        .transpose()
+}
+
+/// `false` if `email` is suppressed, logging why it must not be mailed.
+pub async fn can_be_mailed(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+) -> Result<bool, anyhow::Error> {
+    match suppression_reason(executor, email).await? {
+        Some(reason) => {
+            tracing::warn!(?reason, "Not emailing a suppressed address");
+            Ok(false)
+        }
+        None => Ok(true),
+    }
+}
+
+pub async fn list_suppressed_emails(
+    executor: impl PgExecutor<'_>,
+) -> Result<Vec<SuppressedEmail>, anyhow::Error> {
+    let entries = sqlx::query!(
+        r#"
+        SELECT email, reason, suppressed_at
+        FROM suppressed_emails
+        ORDER BY suppressed_at
+        "#
+    )
+    .fetch_all(executor)
+    .await?;
+    entries
+        .into_iter()
+        .map(|e| {
+            Ok(SuppressedEmail {
+                email: e.email,
+                reason: SuppressionReason::parse(&e.reason)?,
+                suppressed_at: e.suppressed_at,
+            })
+        })
+        .collect()
+}
+
+/// Addresses are matched regardless of case and surrounding whitespace.
+fn email_hash(email: &str) -> String {
+    hex::encode(Sha256::digest(email.trim().to_lowercase().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::email_hash;
+
+    #[test]
+    fn email_hashes_ignore_case_and_surrounding_whitespace() {
+        assert_eq!(
+            email_hash("ursula@gmail.com"),
+            email_hash(" Ursula@Gmail.com ")
+        );
+        assert_ne!(
+            email_hash("ursula@gmail.com"),
+            email_hash("octavia@gmail.com")
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use crate::helpers::{spawn_app, TestApp};
+
+async fn insert_issue(app: &TestApp, title: &str, status: &str) -> String {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, status, published_at
+        )
+        VALUES ($1, $2, 'Sample newsletter', '<p>Sample newsletter</p>', $3, now())
+        RETURNING slug
+        "#,
+        uuid::Uuid::new_v4(),
+        title,
+        status
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to insert issue")
+    .slug
+}
+
+#[actix_rt::test]
+async fn archive_lists_published_issues_only() {
+    let app = spawn_app().await;
+    let published_slug = insert_issue(&app, "Published issue", "published").await;
+    let draft_slug = insert_issue(&app, "Draft issue", "draft").await;
+    let scheduled_slug = insert_issue(&app, "Scheduled issue", "scheduled").await;
+
+    let response = reqwest::get(format!("{}/archive", &app.address))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains(&format!(r#"href="/archive/{}""#, published_slug)));
+    assert!(html.contains("Published issue"));
+    assert!(!html.contains(&draft_slug));
+    assert!(!html.contains(&scheduled_slug));
+}
+
+#[actix_rt::test]
+async fn archive_escapes_issue_titles() {
+    let app = spawn_app().await;
+    insert_issue(&app, "<script>alert(1)</script>", "published").await;
+
+    let html = reqwest::get(format!("{}/archive", &app.address))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(!html.contains("<script>"));
+    assert!(html.contains("&lt;script&gt;"));
+}
+
+#[actix_rt::test]
+async fn archive_is_paginated() {
+    let app = spawn_app().await;
+    for i in 0..21 {
+        insert_issue(&app, &format!("Issue {}", i), "published").await;
+    }
+
+    let first_page = reqwest::get(format!("{}/archive", &app.address))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let second_page = reqwest::get(format!("{}/archive?page=2", &app.address))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert_eq!(first_page.matches("<li>").count(), 20);
+    assert!(first_page.contains(r#"href="/archive?page=2""#));
+    assert_eq!(second_page.matches("<li>").count(), 1);
+    assert!(second_page.contains(r#"href="/archive?page=1""#));
+    assert!(!second_page.contains(r#"href="/archive?page=3""#));
+}
+
+#[actix_rt::test]
+async fn archived_issues_render_their_html_body() {
+    let app = spawn_app().await;
+    let slug = insert_issue(&app, "Published issue", "published").await;
+
+    let response = reqwest::get(format!("{}/archive/{}", &app.address, slug))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/html; charset=utf-8"
+    );
+    let html = response.text().await.unwrap();
+    assert!(html.contains("<h1>Published issue</h1>"));
+    assert!(html.contains("<p>Sample newsletter</p>"));
+}
+
+#[actix_rt::test]
+async fn unpublished_issues_are_not_in_the_archive() {
+    let app = spawn_app().await;
+    let slug = insert_issue(&app, "Draft issue", "draft").await;
+
+    let response = reqwest::get(format!("{}/archive/{}", &app.address, slug))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,99 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Publish an issue whose delivery to the only confirmed subscriber always fails
+async fn create_dead_letter(app: &TestApp) {
+    create_confirmed_subscriber(app).await;
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .named("Failing delivery")
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "text": "Sample newsletter",
+            "html": "<p>Sample newsletter</p>"
+        }
+    });
+    app.post_newsletter(newsletter_request_body)
+        .await
+        .error_for_status()
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_rt::test]
+async fn dead_letters_are_listed() {
+    let app = spawn_app().await;
+    create_dead_letter(&app).await;
+
+    let response = app.get_dead_letters().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let dead_letters: serde_json::Value = response.json().await.unwrap();
+    let dead_letters = dead_letters.as_array().unwrap();
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0]["subscriber_email"], "tdnb@hello.com");
+    assert_eq!(dead_letters[0]["n_attempts"], app.retry_policy.max_attempts);
+}
+
+#[actix_rt::test]
+async fn requeued_dead_letters_are_delivered() {
+    let app = spawn_app().await;
+    create_dead_letter(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let dead_letters: serde_json::Value = app.get_dead_letters().await.json().await.unwrap();
+    let newsletter_issue_id = dead_letters[0]["newsletter_issue_id"].clone();
+
+    let response = app
+        .post_requeue_dead_letters(serde_json::json!({
+            "newsletter_issue_id": newsletter_issue_id
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let outcome: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(outcome["requeued"], 1);
+
+    app.dispatch_all_pending_emails().await;
+
+    let dead_letters: serde_json::Value = app.get_dead_letters().await.json().await.unwrap();
+    assert!(dead_letters.as_array().unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn dead_letter_endpoints_reject_unauthenticated_requests() {
+    let app = spawn_app().await;
+
+    let list_response = reqwest::Client::new()
+        .get(format!("{}/admin/dead_letters", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    let requeue_response = reqwest::Client::new()
+        .post(format!("{}/admin/dead_letters/requeue", &app.address))
+        .json(&serde_json::json!({
+            "newsletter_issue_id": uuid::Uuid::new_v4()
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    for response in [list_response, requeue_response] {
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(
+            r#"Basic realm="admin""#,
+            response.headers()["WWW-Authenticate"]
+        );
+    }
+}
@@ -3,9 +3,19 @@ use argon2::{Argon2, PasswordHasher};
 use once_cell::sync::Lazy;
 use sqlx::types::Uuid;
 use sqlx::{Executor, PgPool};
-use wiremock::MockServer;
-use z2p::configuration::{get_configuration, DatabaseSettings};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use z2p::configuration::{
+    get_configuration, DatabaseSettings, EmailProvider, SubscriptionSettings, WebhookSettings,
+};
+use z2p::email_client::EmailClient;
+use z2p::issue_delivery_worker::{try_execute_task, ExecutionOutcome, RetryPolicy};
+use z2p::issue_scheduler::{try_release_scheduled_issue, ReleaseOutcome};
+use z2p::preference_links::PreferenceLinks;
 use z2p::startup::{get_connection_pool, Application};
+use z2p::subscription_maintenance::{
+    try_send_confirmation_reminder, try_send_queued_confirmation, ReminderOutcome,
+};
 use z2p::telemetry::{get_subscriber, init_subscriber};
 
 static TRACING: Lazy<()> = Lazy::new(|| {
@@ -16,9 +26,9 @@ static TRACING: Lazy<()> = Lazy::new(|| {
 });
 
 pub struct TestUser {
-    user_id: Uuid,
-    username: String,
-    password: String,
+    pub user_id: Uuid,
+    pub username: String,
+    pub password: String,
 }
 
 impl TestUser {
@@ -33,7 +43,7 @@ impl TestUser {
     pub async fn store(&self, pool: &PgPool) {
         let salt = SaltString::generate(&mut rand::thread_rng());
         let password_hash = Argon2::default()
-            .hash_password(&self.password.as_bytes(), &salt)
+            .hash_password(self.password.as_bytes(), &salt)
             .unwrap()
             .to_string();
         sqlx::query!(
@@ -57,6 +67,12 @@ pub struct TestApp {
     pub email_server: MockServer,
     pub test_user: TestUser,
     pub port: u16,
+    pub email_client: EmailClient,
+    pub retry_policy: RetryPolicy,
+    pub base_url: String,
+    pub subscription_settings: SubscriptionSettings,
+    pub preference_links: PreferenceLinks,
+    pub webhook_settings: WebhookSettings,
 }
 
 pub struct ConfirmationLinks {
@@ -65,9 +81,82 @@ pub struct ConfirmationLinks {
 }
 
 impl TestApp {
+    /// Deliver every queued newsletter email.
+    /// The background worker spawned with the application may be holding a task:
+    /// keep going until the queue has actually been drained.
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.retry_policy,
+                &self.base_url,
+                &self.preference_links,
+            )
+            .await
+            .unwrap()
+            {
+                let pending =
+                    sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM issue_delivery_queue"#)
+                        .fetch_one(&self.db_pool)
+                        .await
+                        .unwrap()
+                        .count;
+                if pending == 0 {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+        }
+    }
+
+    /// Send every queued confirmation email, including the ones the background
+    /// worker is still busy with.
+    pub async fn send_queued_confirmations(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue =
+                try_send_queued_confirmation(&self.db_pool, &self.email_client, &self.base_url)
+                    .await
+                    .unwrap()
+            {
+                let queued =
+                    sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM confirmation_email_queue"#)
+                        .fetch_one(&self.db_pool)
+                        .await
+                        .unwrap()
+                        .count;
+                if queued == 0 {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+        }
+    }
+
+    /// Release every scheduled issue whose `send_at` has passed.
+    pub async fn release_scheduled_issues(&self) {
+        while let ReleaseOutcome::IssueReleased =
+            try_release_scheduled_issue(&self.db_pool).await.unwrap()
+        {}
+    }
+
+    /// Remind every unconfirmed subscriber who is due a reminder.
+    pub async fn send_confirmation_reminders(&self) {
+        while let ReminderOutcome::ReminderSent | ReminderOutcome::ReminderFailed =
+            try_send_confirmation_reminder(
+                &self.db_pool,
+                &self.email_client,
+                &self.base_url,
+                &self.subscription_settings,
+            )
+            .await
+            .unwrap()
+        {}
+    }
+
     pub async fn post_subscription(&self, body: String) -> reqwest::Response {
         reqwest::Client::new()
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -75,16 +164,261 @@ impl TestApp {
             .expect("Failed to execute subscription request")
     }
 
+    pub async fn post_subscription_json(&self, body: serde_json::Value) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/subscriptions", &self.address))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute subscription request")
+    }
+
     pub async fn post_newsletter(&self, body: serde_json::Value) -> reqwest::Response {
         reqwest::Client::new()
-            .post(&format!("{}/newsletters", &self.address))
+            .post(format!("{}/newsletters", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute newsletters request")
+    }
+
+    pub async fn post_newsletter_with_idempotency_key(
+        &self,
+        body: serde_json::Value,
+        idempotency_key: &str,
+    ) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/newsletters", &self.address))
             .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .header("Idempotency-Key", idempotency_key)
             .json(&body)
             .send()
             .await
             .expect("Failed to execute newsletters request")
     }
 
+    pub async fn get_issue_status(&self, newsletter_issue_id: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!(
+                "{}/newsletters/{}",
+                &self.address, newsletter_issue_id
+            ))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to execute issue status request")
+    }
+
+    pub async fn post_cancel_issue(&self, newsletter_issue_id: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!(
+                "{}/newsletters/{}/cancel",
+                &self.address, newsletter_issue_id
+            ))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to execute cancel request")
+    }
+
+    pub async fn put_issue_schedule(
+        &self,
+        newsletter_issue_id: &str,
+        body: serde_json::Value,
+    ) -> reqwest::Response {
+        reqwest::Client::new()
+            .put(format!(
+                "{}/newsletters/{}/schedule",
+                &self.address, newsletter_issue_id
+            ))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute reschedule request")
+    }
+
+    pub async fn post_draft(&self, body: serde_json::Value) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/newsletters/drafts", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute create draft request")
+    }
+
+    pub async fn get_drafts(&self) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!("{}/newsletters/drafts", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to execute list drafts request")
+    }
+
+    pub async fn get_draft(&self, newsletter_issue_id: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!(
+                "{}/newsletters/drafts/{}",
+                &self.address, newsletter_issue_id
+            ))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to execute get draft request")
+    }
+
+    pub async fn put_draft(
+        &self,
+        newsletter_issue_id: &str,
+        body: serde_json::Value,
+    ) -> reqwest::Response {
+        reqwest::Client::new()
+            .put(format!(
+                "{}/newsletters/drafts/{}",
+                &self.address, newsletter_issue_id
+            ))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute update draft request")
+    }
+
+    pub async fn delete_draft(&self, newsletter_issue_id: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .delete(format!(
+                "{}/newsletters/drafts/{}",
+                &self.address, newsletter_issue_id
+            ))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to execute delete draft request")
+    }
+
+    pub async fn post_publish_draft(&self, newsletter_issue_id: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!(
+                "{}/newsletters/drafts/{}/publish",
+                &self.address, newsletter_issue_id
+            ))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to execute publish draft request")
+    }
+
+    pub async fn get_dead_letters(&self) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!("{}/admin/dead_letters", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to execute dead letters request")
+    }
+
+    pub async fn post_requeue_dead_letters(&self, body: serde_json::Value) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/admin/dead_letters/requeue", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute requeue request")
+    }
+
+    pub async fn post_subscriber_import(&self, query: &str, csv: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!(
+                "{}/admin/subscribers/import?{}",
+                &self.address, query
+            ))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .header("Content-Type", "text/csv")
+            .body(csv.to_owned())
+            .send()
+            .await
+            .expect("Failed to execute import request")
+    }
+
+    pub async fn get_subscriber_export(&self, format: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!(
+                "{}/admin/subscribers/export?format={}",
+                &self.address, format
+            ))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to execute export request")
+    }
+
+    pub async fn get_subscriber_data(&self, email: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!("{}/admin/subscribers/data", &self.address))
+            .query(&[("email", email)])
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to execute subscriber data request")
+    }
+
+    pub async fn post_erase_subscriber(&self, email: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/admin/subscribers/erase", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .json(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .expect("Failed to execute erase request")
+    }
+
+    pub async fn get_suppressions(&self) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!("{}/admin/suppressions", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to execute suppression list request")
+    }
+
+    pub async fn post_suppression(&self, body: serde_json::Value) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/admin/suppressions", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute suppression request")
+    }
+
+    pub async fn post_remove_suppression(&self, email: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/admin/suppressions/remove", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .json(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .expect("Failed to execute suppression removal request")
+    }
+
+    pub async fn post_postmark_webhook(&self, payload: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/webhooks/postmark", &self.address))
+            .basic_auth(
+                &self.webhook_settings.username,
+                Some(&self.webhook_settings.password),
+            )
+            .header("Content-Type", "application/json")
+            .body(payload.to_owned())
+            .send()
+            .await
+            .expect("Failed to execute webhook request")
+    }
+
     pub async fn get_confirmation_links(
         &self,
         email_request: &wiremock::Request,
@@ -106,8 +440,8 @@ impl TestApp {
             confirmation_link
         };
 
-        let plain_text = get_link(&body["TextBody"].as_str().unwrap());
-        let html = get_link(&body["HtmlBody"].as_str().unwrap());
+        let plain_text = get_link(body["TextBody"].as_str().unwrap());
+        let html = get_link(body["HtmlBody"].as_str().unwrap());
         ConfirmationLinks { html, plain_text }
     }
 }
@@ -121,7 +455,12 @@ pub async fn spawn_app() -> TestApp {
         let mut c = get_configuration().expect("Failed to read configuration");
         c.database.database_name = Uuid::new_v4().to_string();
         c.application.port = 0;
+        // Send emails to the mock server rather than the local outbox
+        c.email_client.provider = EmailProvider::Postmark;
         c.email_client.base_url = email_server.uri();
+        // Retry failed deliveries straight away
+        c.issue_delivery.max_attempts = 3;
+        c.issue_delivery.retry_base_delay_milliseconds = 0;
         c
     };
 
@@ -136,7 +475,7 @@ pub async fn spawn_app() -> TestApp {
     // tokio's runtime is spun up by actix_rt
     //
     // Cleanup not required as all tokio tasks are dropped when tokio runtime is shut down
-    let _ = tokio::spawn(application.run_until_stopped());
+    tokio::spawn(application.run_until_stopped());
 
     let test_user = TestUser::generate();
     let test_app = TestApp {
@@ -145,11 +484,51 @@ pub async fn spawn_app() -> TestApp {
         email_server,
         test_user,
         port: application_port,
+        email_client: configuration.email_client.clone().client(),
+        retry_policy: configuration.issue_delivery.retry_policy(),
+        base_url: configuration.application.base_url.clone(),
+        subscription_settings: configuration.subscriptions.clone(),
+        preference_links: configuration.preference_links(),
+        webhook_settings: configuration.webhooks.clone(),
     };
     test_app.test_user.store(&test_app.db_pool).await;
     test_app
 }
 
+// Use app API to create unconfirmed subscriber
+pub async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    let body = "name=bn&email=tdnb%40hello.com";
+    // Scoped mock guard, doesn't interfere with other mocked servers when it goes out of scope
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .named("Create unconfirmed subscriber")
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscription(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_confirmation_links(email_request).await
+}
+
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    let confirmation_link = create_unconfirmed_subscriber(app).await;
+    reqwest::get(confirmation_link.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
 pub async fn configure_database(config: &DatabaseSettings) -> PgPool {
     let connection_pool = PgPool::connect(&config.connection_string_without_db())
         .await
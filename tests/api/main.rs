@@ -1,5 +1,19 @@
+mod archive;
+mod dead_letters;
 mod health_check;
 mod helpers;
 mod newsletter;
+mod newsletter_drafts;
+mod newsletter_schedule;
+mod newsletter_status;
+mod subscriber_data;
+mod subscriber_export;
+mod subscriber_import;
 mod subscription_confirm;
+mod subscription_consent;
+mod subscription_maintenance;
+mod subscription_preferences;
+mod subscription_unsubscribe;
 mod subscriptions;
+mod suppressions;
+mod webhooks;
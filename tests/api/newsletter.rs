@@ -1,4 +1,4 @@
-use crate::helpers::{spawn_app, ConfirmationLinks, TestApp};
+use crate::helpers::{create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app};
 use wiremock::matchers::{any, method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -24,7 +24,8 @@ async fn newsletters_are_not_delievered_to_unconfirmed_subscribers() {
 
     let response = app.post_newsletter(newsletter_request_body).await;
 
-    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
 }
 
 #[actix_rt::test]
@@ -80,7 +81,41 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
 
     let response = app.post_newsletter(newsletter_request_body).await;
 
-    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_rt::test]
+async fn newsletters_are_queued_instead_of_being_sent_inline() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "text": "Sample newsletter",
+            "html": "<p>Sample newsletter</p>"
+        }
+    });
+
+    let response = app.post_newsletter(newsletter_request_body).await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let queued = sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].subscriber_email, "tdnb@hello.com");
+
+    app.dispatch_all_pending_emails().await;
 }
 
 #[actix_rt::test]
@@ -96,7 +131,7 @@ async fn request_missing_authorization_is_rejected() {
     });
 
     let response = reqwest::Client::new()
-        .post(&format!("{}/newsletters", &app.address))
+        .post(format!("{}/newsletters", &app.address))
         .json(&newsletter_request_body)
         .send()
         .await
@@ -109,36 +144,305 @@ async fn request_missing_authorization_is_rejected() {
     )
 }
 
-// Use app API to create unconfirmed subscriber
-async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
-    let body = "name=bn&email=tdnb%40hello.com";
-    // Scoped mock guard, doesn't interfere with other mocked servers when it goes out of scope
-    let _mock_guard = Mock::given(path("/email"))
+#[actix_rt::test]
+async fn newsletter_creation_is_idempotent() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "text": "Sample newsletter",
+            "html": "<p>Sample newsletter</p>"
+        }
+    });
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+
+    // Submit the newsletter
+    let response = app
+        .post_newsletter_with_idempotency_key(newsletter_request_body.clone(), &idempotency_key)
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    // Submit it again with the same key, the email is not sent a second time
+    let response = app
+        .post_newsletter_with_idempotency_key(newsletter_request_body, &idempotency_key)
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_rt::test]
+async fn concurrent_newsletter_submissions_are_handled_gracefully() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "text": "Sample newsletter",
+            "html": "<p>Sample newsletter</p>"
+        }
+    });
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+
+    let response1 =
+        app.post_newsletter_with_idempotency_key(newsletter_request_body.clone(), &idempotency_key);
+    let response2 =
+        app.post_newsletter_with_idempotency_key(newsletter_request_body, &idempotency_key);
+    let (response1, response2) = tokio::join!(response1, response2);
+
+    assert_eq!(response1.status(), response2.status());
+    assert_eq!(
+        response1.text().await.unwrap(),
+        response2.text().await.unwrap()
+    );
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_rt::test]
+async fn newsletters_with_an_invalid_idempotency_key_are_rejected() {
+    let app = spawn_app().await;
+
+    let newsletter_request_body = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "text": "Sample newsletter",
+            "html": "<p>Sample newsletter</p>"
+        }
+    });
+
+    let response = app
+        .post_newsletter_with_idempotency_key(newsletter_request_body, &"a".repeat(50))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_rt::test]
+async fn failed_deliveries_are_retried() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    // The first attempt fails, the retry goes through
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
         .expect(1)
-        .named("Create unconfirmed subscriber")
-        .mount_as_scoped(&app.email_server)
+        .mount(&app.email_server)
         .await;
-    app.post_subscription(body.into())
+
+    let newsletter_request_body = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "text": "Sample newsletter",
+            "html": "<p>Sample newsletter</p>"
+        }
+    });
+    let response = app.post_newsletter(newsletter_request_body).await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    app.dispatch_all_pending_emails().await;
+
+    let dead_letters = sqlx::query!("SELECT subscriber_email FROM issue_delivery_dead_letters")
+        .fetch_all(&app.db_pool)
         .await
-        .error_for_status()
         .unwrap();
-    let email_request = &app
-        .email_server
-        .received_requests()
+    assert!(dead_letters.is_empty());
+}
+
+#[actix_rt::test]
+async fn deliveries_are_dead_lettered_once_they_run_out_of_attempts() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(app.retry_policy.max_attempts as u64)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "text": "Sample newsletter",
+            "html": "<p>Sample newsletter</p>"
+        }
+    });
+    let response = app.post_newsletter(newsletter_request_body).await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    app.dispatch_all_pending_emails().await;
+
+    let dead_letter =
+        sqlx::query!("SELECT subscriber_email, n_attempts FROM issue_delivery_dead_letters")
+            .fetch_one(&app.db_pool)
+            .await
+            .expect("Failed to fetch dead letter");
+    assert_eq!(dead_letter.subscriber_email, "tdnb@hello.com");
+    assert_eq!(dead_letter.n_attempts, app.retry_policy.max_attempts);
+}
+
+#[actix_rt::test]
+async fn merge_fields_are_rendered_for_each_subscriber() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "text": "Hi {{ name }}, unsubscribe at {{ unsubscribe_url }}",
+            "html": "<p>Hi {{ name }}, this was sent to {{ email }}</p>"
+        }
+    });
+    let response = app.post_newsletter(newsletter_request_body).await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+
+    // The first request recorded is the confirmation email
+    let received_requests = app.email_server.received_requests().await.unwrap();
+    let email_request = received_requests.last().unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    // Followed by the link to the preference page
+    assert!(body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("<p>Hi bn, this was sent to tdnb@hello.com</p>\n"));
+    let unsubscribe_token = sqlx::query!("SELECT unsubscribe_token FROM subscriptions")
+        .fetch_one(&app.db_pool)
         .await
         .unwrap()
-        .pop()
-        .unwrap();
-    app.get_confirmation_links(email_request).await
+        .unsubscribe_token;
+    assert!(body["TextBody"].as_str().unwrap().starts_with(&format!(
+        "Hi bn, unsubscribe at {}/subscriptions/unsubscribe?unsubscribe_token={}\n",
+        app.base_url, unsubscribe_token
+    )));
 }
 
-async fn create_confirmed_subscriber(app: &TestApp) {
-    let confirmation_link = create_unconfirmed_subscriber(&app).await;
-    reqwest::get(confirmation_link.html)
+#[actix_rt::test]
+async fn newsletters_with_unknown_merge_fields_are_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let test_cases = vec![
+        ("Hi {{ nickname }}", "<p>Hi</p>", "unknown field in text"),
+        ("Hi", "<p>Hi {{ first_name }}</p>", "unknown field in html"),
+        ("Hi {{ name", "<p>Hi</p>", "unclosed field"),
+    ];
+    for (text, html, description) in test_cases {
+        let response = app
+            .post_newsletter(serde_json::json!({
+                "title": "Newsletter #1",
+                "content": { "text": text, "html": html }
+            }))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 when the content had an {}",
+            description
+        );
+    }
+
+    let n_queued = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
         .await
         .unwrap()
-        .error_for_status()
-        .unwrap();
+        .count;
+    assert_eq!(n_queued, 0);
+}
+
+#[actix_rt::test]
+async fn markdown_newsletters_are_delivered_as_html_and_text() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "markdown": "# Hi {{ name }}\n\nSome **news** <b onclick=\"steal()\">today</b>"
+        }
+    });
+    let response = app.post_newsletter(newsletter_request_body).await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+
+    let received_requests = app.email_server.received_requests().await.unwrap();
+    let email_request = received_requests.last().unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("<h1>Hi bn</h1>\n<p>Some <strong>news</strong> <b>today</b></p>\n"));
+    assert!(body["TextBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("Hi bn\n\nSome news today\n"));
+}
+
+#[actix_rt::test]
+async fn newsletters_must_have_either_markdown_or_text_and_html() {
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({ "markdown": "Hi", "text": "Hi", "html": "<p>Hi</p>" }),
+            "markdown alongside text and html",
+        ),
+        (serde_json::json!({ "text": "Hi" }), "text without html"),
+        (serde_json::json!({}), "no body at all"),
+    ];
+    for (content, description) in test_cases {
+        let response = app
+            .post_newsletter(serde_json::json!({ "title": "Newsletter #1", "content": content }))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 when the content had {}",
+            description
+        );
+    }
 }
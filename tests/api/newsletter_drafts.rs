@@ -0,0 +1,165 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp, TestUser};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn create_draft(app: &TestApp) -> String {
+    let draft = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "text": "Sample newsletter",
+            "html": "<p>Sample newsletter</p>"
+        }
+    });
+    let response = app.post_draft(draft).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let body: serde_json::Value = response.json().await.unwrap();
+    body["newsletter_issue_id"].as_str().unwrap().to_owned()
+}
+
+#[actix_rt::test]
+async fn drafts_can_be_created_read_updated_and_deleted() {
+    let app = spawn_app().await;
+
+    let newsletter_issue_id = create_draft(&app).await;
+    let draft: serde_json::Value = app
+        .get_draft(&newsletter_issue_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(draft["title"], "Newsletter #1");
+    assert_eq!(draft["content"]["text"], "Sample newsletter");
+
+    let response = app
+        .put_draft(
+            &newsletter_issue_id,
+            serde_json::json!({
+               "title": "Newsletter #1 (edited)",
+                "content": {
+                    "text": "Edited newsletter",
+                    "html": "<p>Edited newsletter</p>"
+                }
+            }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let draft: serde_json::Value = app
+        .get_draft(&newsletter_issue_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(draft["title"], "Newsletter #1 (edited)");
+    assert_eq!(draft["content"]["html"], "<p>Edited newsletter</p>");
+
+    let drafts: serde_json::Value = app.get_drafts().await.json().await.unwrap();
+    assert_eq!(drafts.as_array().unwrap().len(), 1);
+    assert_eq!(drafts[0]["newsletter_issue_id"], newsletter_issue_id);
+
+    let response = app.delete_draft(&newsletter_issue_id).await;
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app.get_draft(&newsletter_issue_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn drafts_are_not_delivered() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    create_draft(&app).await;
+    app.release_scheduled_issues().await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_rt::test]
+async fn published_drafts_are_delivered() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_issue_id = create_draft(&app).await;
+    let response = app.post_publish_draft(&newsletter_issue_id).await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+
+    let status: serde_json::Value = app
+        .get_issue_status(&newsletter_issue_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["status"], "published");
+    assert_eq!(status["deliveries"]["sent"], 1);
+
+    // It is no longer a draft: it can't be edited or published again
+    let drafts: serde_json::Value = app.get_drafts().await.json().await.unwrap();
+    assert!(drafts.as_array().unwrap().is_empty());
+    let response = app.post_publish_draft(&newsletter_issue_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn drafts_are_only_visible_to_their_author() {
+    let app = spawn_app().await;
+    let newsletter_issue_id = create_draft(&app).await;
+
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    let response = reqwest::Client::new()
+        .get(format!(
+            "{}/newsletters/drafts/{}",
+            &app.address, newsletter_issue_id
+        ))
+        .basic_auth(&other_user.username, Some(&other_user.password))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn drafts_require_authentication() {
+    let app = spawn_app().await;
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/newsletters/drafts", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_rt::test]
+async fn drafts_with_unknown_merge_fields_are_rejected() {
+    let app = spawn_app().await;
+    let invalid_content = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "text": "Hi {{ nickname }}",
+            "html": "<p>Hi {{ nickname }}</p>"
+        }
+    });
+
+    let response = app.post_draft(invalid_content.clone()).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let newsletter_issue_id = create_draft(&app).await;
+    let response = app.put_draft(&newsletter_issue_id, invalid_content).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,196 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+use chrono::{Duration, Utc};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn schedule_newsletter(app: &TestApp) -> String {
+    let newsletter_request_body = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "text": "Sample newsletter",
+            "html": "<p>Sample newsletter</p>"
+        },
+        "send_at": Utc::now() + Duration::days(3)
+    });
+    let response = app.post_newsletter(newsletter_request_body).await;
+    assert_eq!(response.status().as_u16(), 202);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["send_at"].is_string());
+    body["newsletter_issue_id"].as_str().unwrap().to_owned()
+}
+
+/// Pretend the scheduled time has come
+async fn make_due(app: &TestApp, newsletter_issue_id: &str) {
+    sqlx::query!(
+        "UPDATE newsletter_issues SET send_at = now() - interval '1 minute' WHERE newsletter_issue_id = $1",
+        uuid::Uuid::parse_str(newsletter_issue_id).unwrap()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[actix_rt::test]
+async fn scheduled_issues_are_held_until_send_at() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_issue_id = schedule_newsletter(&app).await;
+    app.release_scheduled_issues().await;
+    app.dispatch_all_pending_emails().await;
+
+    let status: serde_json::Value = app
+        .get_issue_status(&newsletter_issue_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["status"], "scheduled");
+    assert!(status["published_at"].is_null());
+}
+
+#[actix_rt::test]
+async fn scheduled_issues_are_delivered_once_due() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_issue_id = schedule_newsletter(&app).await;
+    make_due(&app, &newsletter_issue_id).await;
+    app.release_scheduled_issues().await;
+    app.dispatch_all_pending_emails().await;
+
+    let status: serde_json::Value = app
+        .get_issue_status(&newsletter_issue_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["status"], "published");
+    assert_eq!(status["deliveries"]["sent"], 1);
+}
+
+#[actix_rt::test]
+async fn cancelled_issues_are_never_delivered() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_issue_id = schedule_newsletter(&app).await;
+    let response = app.post_cancel_issue(&newsletter_issue_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    make_due(&app, &newsletter_issue_id).await;
+    app.release_scheduled_issues().await;
+    app.dispatch_all_pending_emails().await;
+
+    let status: serde_json::Value = app
+        .get_issue_status(&newsletter_issue_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["status"], "cancelled");
+}
+
+#[actix_rt::test]
+async fn scheduled_issues_can_be_rescheduled() {
+    let app = spawn_app().await;
+    let newsletter_issue_id = schedule_newsletter(&app).await;
+    let send_at = Utc::now() + Duration::days(7);
+
+    let response = app
+        .put_issue_schedule(
+            &newsletter_issue_id,
+            serde_json::json!({ "send_at": send_at }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let saved = sqlx::query!(
+        "SELECT send_at FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        uuid::Uuid::parse_str(&newsletter_issue_id).unwrap()
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        saved.send_at.unwrap().timestamp_millis(),
+        send_at.timestamp_millis()
+    );
+}
+
+#[actix_rt::test]
+async fn rescheduling_to_the_past_is_rejected() {
+    let app = spawn_app().await;
+    let newsletter_issue_id = schedule_newsletter(&app).await;
+
+    let response = app
+        .put_issue_schedule(
+            &newsletter_issue_id,
+            serde_json::json!({ "send_at": Utc::now() - Duration::hours(1) }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_rt::test]
+async fn published_issues_cannot_be_cancelled_or_rescheduled() {
+    let app = spawn_app().await;
+    let newsletter_request_body = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "text": "Sample newsletter",
+            "html": "<p>Sample newsletter</p>"
+        }
+    });
+    let body: serde_json::Value = app
+        .post_newsletter(newsletter_request_body)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let newsletter_issue_id = body["newsletter_issue_id"].as_str().unwrap();
+
+    let cancel_response = app.post_cancel_issue(newsletter_issue_id).await;
+    let reschedule_response = app
+        .put_issue_schedule(
+            newsletter_issue_id,
+            serde_json::json!({ "send_at": Utc::now() + Duration::days(1) }),
+        )
+        .await;
+
+    assert_eq!(cancel_response.status().as_u16(), 409);
+    assert_eq!(reschedule_response.status().as_u16(), 409);
+}
+
+#[actix_rt::test]
+async fn cancelling_an_unknown_issue_returns_404() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_cancel_issue(&uuid::Uuid::new_v4().to_string())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,133 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn publish_newsletter(app: &TestApp) -> String {
+    let newsletter_request_body = serde_json::json!({
+       "title": "Newsletter #1",
+        "content": {
+            "text": "Sample newsletter",
+            "html": "<p>Sample newsletter</p>"
+        }
+    });
+    let response = app.post_newsletter(newsletter_request_body).await;
+    assert_eq!(response.status().as_u16(), 202);
+    let body: serde_json::Value = response.json().await.unwrap();
+    body["newsletter_issue_id"].as_str().unwrap().to_owned()
+}
+
+#[actix_rt::test]
+async fn published_issues_are_persisted_with_their_author() {
+    let app = spawn_app().await;
+
+    let newsletter_issue_id = publish_newsletter(&app).await;
+
+    let newsletter_issue_id = uuid::Uuid::parse_str(&newsletter_issue_id).unwrap();
+    let saved = sqlx::query!(
+        r#"
+        SELECT title, text_content, html_content, user_id
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved issue");
+    assert_eq!(saved.title, "Newsletter #1");
+    assert_eq!(saved.text_content, "Sample newsletter");
+    assert_eq!(saved.html_content, "<p>Sample newsletter</p>");
+    assert_eq!(saved.user_id, Some(app.test_user.user_id));
+}
+
+#[actix_rt::test]
+async fn issue_status_reports_delivery_progress() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_issue_id = publish_newsletter(&app).await;
+
+    let status: serde_json::Value = app
+        .get_issue_status(&newsletter_issue_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["title"], "Newsletter #1");
+    assert_eq!(status["author"], app.test_user.user_id.to_string());
+    assert_eq!(status["deliveries"]["queued"], 1);
+    assert_eq!(status["deliveries"]["sent"], 0);
+
+    app.dispatch_all_pending_emails().await;
+
+    let status: serde_json::Value = app
+        .get_issue_status(&newsletter_issue_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["deliveries"]["queued"], 0);
+    assert_eq!(status["deliveries"]["sent"], 1);
+    assert_eq!(status["deliveries"]["failed"], 0);
+    assert_eq!(status["deliveries"]["skipped"], 0);
+}
+
+#[actix_rt::test]
+async fn issue_status_counts_failed_deliveries() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_issue_id = publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let status: serde_json::Value = app
+        .get_issue_status(&newsletter_issue_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["deliveries"]["queued"], 0);
+    assert_eq!(status["deliveries"]["sent"], 0);
+    assert_eq!(status["deliveries"]["failed"], 1);
+}
+
+#[actix_rt::test]
+async fn issue_status_returns_404_for_unknown_issues() {
+    let app = spawn_app().await;
+
+    let response = app
+        .get_issue_status(&uuid::Uuid::new_v4().to_string())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn issue_status_requires_authentication() {
+    let app = spawn_app().await;
+    let newsletter_issue_id = publish_newsletter(&app).await;
+
+    let response = reqwest::Client::new()
+        .get(format!(
+            "{}/newsletters/{}",
+            &app.address, newsletter_issue_id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,216 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+async fn deliver_a_newsletter(app: &TestApp) {
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(serde_json::json!({
+        "title": "Newsletter #1",
+        "content": {
+            "text": "Sample newsletter",
+            "html": "<p>Sample newsletter</p>"
+        }
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+}
+
+async fn preferences_token(app: &TestApp) -> String {
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    let url = app.preference_links.url_for(subscriber_id);
+    url.split_once("token=").unwrap().1.to_owned()
+}
+
+#[actix_rt::test]
+async fn subscriber_data_includes_the_subscription_tokens_and_deliveries() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    deliver_a_newsletter(&app).await;
+
+    let response = app.get_subscriber_data("tdnb@hello.com").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let data: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(data["subscription"]["email"], "tdnb@hello.com");
+    assert_eq!(data["subscription"]["name"], "bn");
+    assert_eq!(data["subscription"]["status"], "confirmed");
+    assert_eq!(data["confirmation_tokens"].as_array().unwrap().len(), 1);
+    assert!(!data["confirmation_tokens"][0]["consumed_at"].is_null());
+    let deliveries = data["deliveries"].as_array().unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0]["outcome"], "sent");
+}
+
+#[actix_rt::test]
+async fn subscriber_data_of_an_unknown_email_returns_404() {
+    let app = spawn_app().await;
+
+    let response = app.get_subscriber_data("nobody@hello.com").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn subscriber_data_requires_admin_credentials() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let data = reqwest::Client::new()
+        .get(format!("{}/admin/subscribers/data", &app.address))
+        .query(&[("email", "tdnb@hello.com")])
+        .send()
+        .await
+        .unwrap();
+    let erase = reqwest::Client::new()
+        .post(format!("{}/admin/subscribers/erase", &app.address))
+        .json(&serde_json::json!({ "email": "tdnb@hello.com" }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(data.status().as_u16(), 401);
+    assert_eq!(erase.status().as_u16(), 401);
+    let n_subscribers = sqlx::query!("SELECT COUNT(*) AS n FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, Some(1));
+}
+
+#[actix_rt::test]
+async fn erasing_a_subscriber_removes_their_data_but_keeps_delivery_counts() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    deliver_a_newsletter(&app).await;
+
+    let response = app.post_erase_subscriber("tdnb@hello.com").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let n_subscribers = sqlx::query!("SELECT COUNT(*) AS n FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, Some(0));
+    let n_tokens = sqlx::query!("SELECT COUNT(*) AS n FROM subscription_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_tokens, Some(0));
+    let deliveries = sqlx::query!("SELECT subscriber_email FROM issue_delivery_log")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_ne!(deliveries[0].subscriber_email, "tdnb@hello.com");
+    assert_eq!(
+        app.get_subscriber_data("tdnb@hello.com")
+            .await
+            .status()
+            .as_u16(),
+        404
+    );
+}
+
+#[actix_rt::test]
+async fn erasing_an_unknown_email_returns_404() {
+    let app = spawn_app().await;
+
+    let response = app.post_erase_subscriber("nobody@hello.com").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn erased_subscribers_are_not_imported_again() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_erase_subscriber("tdnb@hello.com")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let response = app
+        .post_subscriber_import(
+            "status=confirmed&consent_source=old_provider",
+            "email,name\nTDNB@hello.com,bn\noctavia@butler.com,Octavia\n",
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["accepted"], 1);
+    assert_eq!(report["erased"], 1);
+    assert_eq!(report["rows"][0]["outcome"], "erased");
+    let emails: Vec<_> = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.email)
+        .collect();
+    assert_eq!(emails, vec!["octavia@butler.com"]);
+}
+
+#[actix_rt::test]
+async fn subscribers_can_download_their_own_data() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preferences_token(&app).await;
+
+    let response = reqwest::get(format!(
+        "{}/subscriptions/preferences/data?token={}",
+        app.address, token
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Disposition"]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment"));
+    let data: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(data["subscription"]["email"], "tdnb@hello.com");
+}
+
+#[actix_rt::test]
+async fn subscribers_can_erase_their_own_data() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preferences_token(&app).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/subscriptions/preferences/erase", app.address))
+        .form(&[("token", token.as_str())])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let n_subscribers = sqlx::query!("SELECT COUNT(*) AS n FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .n;
+    assert_eq!(n_subscribers, Some(0));
+    // The preference link belonged to the erased subscriber
+    let response = reqwest::get(format!(
+        "{}/subscriptions/preferences?token={}",
+        app.address, token
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
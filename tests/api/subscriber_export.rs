@@ -0,0 +1,110 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+
+/// One confirmed subscriber from the form and one pending from an import.
+async fn create_subscribers(app: &TestApp) {
+    create_confirmed_subscriber(app).await;
+    app.post_subscriber_import(
+        "status=confirmed&consent_source=old_provider",
+        "email,name\n\"ursula_le_guin@gmail.com\",\"Le Guin, Ursula\"\n",
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+}
+
+#[actix_rt::test]
+async fn subscribers_are_exported_as_csv() {
+    let app = spawn_app().await;
+    create_subscribers(&app).await;
+
+    let response = app.get_subscriber_export("csv").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/csv; charset=utf-8"
+    );
+    let body = response.text().await.unwrap();
+    let mut reader = csv::Reader::from_reader(body.as_bytes());
+    assert_eq!(
+        reader.headers().unwrap(),
+        vec![
+            "email",
+            "name",
+            "status",
+            "subscribed_at",
+            "confirmed_at",
+            "consent_source",
+            "signup_form",
+            "consent_text_version",
+            "signup_ip_address",
+            "signup_user_agent",
+            "confirmation_ip_address",
+            "confirmation_user_agent"
+        ]
+    );
+    let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(&rows[0][0], "tdnb@hello.com");
+    assert_eq!(&rows[0][2], "confirmed");
+    assert!(!rows[0][4].is_empty());
+    assert_eq!(&rows[1][1], "Le Guin, Ursula");
+    assert_eq!(&rows[1][5], "old_provider");
+    assert_eq!(&rows[0][6], "subscription_form");
+    assert_eq!(&rows[0][8], "127.0.0.1");
+    assert_eq!(&rows[0][10], "127.0.0.1");
+    assert!(rows[1][6].is_empty());
+}
+
+#[actix_rt::test]
+async fn subscribers_are_exported_as_json() {
+    let app = spawn_app().await;
+    create_subscribers(&app).await;
+
+    let response = app.get_subscriber_export("json").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let subscribers: serde_json::Value = response.json().await.unwrap();
+    let subscribers = subscribers.as_array().unwrap();
+    assert_eq!(subscribers.len(), 2);
+    assert_eq!(subscribers[0]["email"], "tdnb@hello.com");
+    assert_eq!(subscribers[0]["status"], "confirmed");
+    assert!(subscribers[0]["confirmed_at"].is_string());
+    assert_eq!(subscribers[1]["consent_source"], "old_provider");
+    assert_eq!(subscribers[0]["consent_text_version"], "2023-01");
+    assert!(subscribers[1]["signup_form"].is_null());
+}
+
+#[actix_rt::test]
+async fn an_empty_list_is_exported_as_valid_json() {
+    let app = spawn_app().await;
+
+    let response = app.get_subscriber_export("json").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let subscribers: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscribers, serde_json::json!([]));
+}
+
+#[actix_rt::test]
+async fn exports_in_an_unknown_format_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app.get_subscriber_export("xml").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_rt::test]
+async fn exports_require_authentication() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!(
+        "{}/admin/subscribers/export?format=csv",
+        &app.address
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
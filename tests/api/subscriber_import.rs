@@ -0,0 +1,204 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app};
+use sqlx::types::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const CSV: &str = "email,name
+ursula_le_guin@gmail.com,Ursula
+not-an-email,Nobody
+tdnb@hello.com,bn
+octavia@butler.com,Octavia
+ursula_le_guin@gmail.com,Ursula again
+";
+
+#[actix_rt::test]
+async fn imported_rows_are_reported_one_by_one() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let response = app
+        .post_subscriber_import("status=confirmed&consent_source=old_provider", CSV)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["accepted"], 2);
+    assert_eq!(report["duplicate"], 2);
+    assert_eq!(report["invalid"], 1);
+    let outcomes: Vec<_> = report["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| (r["line"].as_u64().unwrap(), r["outcome"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            (2, "accepted"),
+            (3, "invalid"),
+            (4, "duplicate"),
+            (5, "accepted"),
+            (6, "duplicate")
+        ]
+    );
+    assert!(report["rows"][1]["error"]
+        .as_str()
+        .unwrap()
+        .contains("not-an-email"));
+}
+
+#[actix_rt::test]
+async fn subscribers_can_be_imported_as_confirmed_with_their_consent_source() {
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriber_import(
+            "status=confirmed&consent_source=old_provider",
+            "email,name\nursula_le_guin@gmail.com,Ursula\n",
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT email, name, status, consent_source FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.name, "Ursula");
+    assert_eq!(saved.status, "confirmed");
+    assert_eq!(saved.consent_source.as_deref(), Some("old_provider"));
+}
+
+#[actix_rt::test]
+async fn subscribers_imported_as_pending_are_sent_a_confirmation_email() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriber_import(
+            "status=pending",
+            "email,name\nursula_le_guin@gmail.com,Ursula\noctavia@butler.com,Octavia\n",
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    app.send_queued_confirmations().await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request).await;
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let statuses: Vec<_> = sqlx::query!("SELECT status FROM subscriptions ORDER BY status")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.status)
+        .collect();
+    assert_eq!(statuses, vec!["confirmed", "pending_confirmation"]);
+}
+
+#[actix_rt::test]
+async fn imports_do_not_wait_for_confirmation_emails_to_be_sent() {
+    let app = spawn_app().await;
+    // Emails still being sent by the background worker stay in the queue
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(2)))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_subscriber_import("status=pending", CSV).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["accepted"], 3);
+    let queued = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM confirmation_email_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(queued, 3);
+}
+
+#[actix_rt::test]
+async fn queued_confirmation_emails_are_skipped_for_subscribers_no_longer_pending() {
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+        VALUES ($1, 'ursula_le_guin@gmail.com', 'Ursula', now(), 'unsubscribed', 'token')
+        "#,
+        subscriber_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "INSERT INTO confirmation_email_queue (subscriber_id, enqueued_at) VALUES ($1, now())",
+        subscriber_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    app.send_queued_confirmations().await;
+}
+
+#[actix_rt::test]
+async fn imports_are_rejected_when_they_cannot_be_processed() {
+    let app = spawn_app().await;
+    let test_cases = vec![
+        ("status=confirmed", CSV, "no consent source"),
+        ("status=unknown", CSV, "an unknown status"),
+        (
+            "status=pending",
+            "mail,full_name\na@b.com,A\n",
+            "no email,name header",
+        ),
+    ];
+    for (query, csv, description) in test_cases {
+        let response = app.post_subscriber_import(query, csv).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The import did not fail with 400 with {}",
+            description
+        );
+    }
+}
+
+#[actix_rt::test]
+async fn imports_require_authentication() {
+    let app = spawn_app().await;
+
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/admin/subscribers/import?status=pending",
+            &app.address
+        ))
+        .body(CSV)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,116 @@
+use crate::helpers::{
+    create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app, TestApp,
+};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+struct Consent {
+    event: String,
+    source: String,
+    consent_text_version: String,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+}
+
+async fn consent_log(app: &TestApp) -> Vec<Consent> {
+    sqlx::query_as!(
+        Consent,
+        r#"
+        SELECT event, source, consent_text_version, ip_address, user_agent
+        FROM subscription_consents
+        ORDER BY recorded_at
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+#[actix_rt::test]
+async fn subscribing_records_how_consent_was_given() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    reqwest::Client::new()
+        .post(format!("{}/subscriptions", &app.address))
+        .header("User-Agent", "Mozilla/5.0 (Test)")
+        .form(&[
+            ("name", "bn"),
+            ("email", "tdnb@hello.com"),
+            ("source", "footer_form"),
+            ("consent_text_version", "2022-11"),
+        ])
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let consents = consent_log(&app).await;
+    assert_eq!(consents.len(), 1);
+    assert_eq!(consents[0].event, "subscribed");
+    assert_eq!(consents[0].source, "footer_form");
+    assert_eq!(consents[0].consent_text_version, "2022-11");
+    assert_eq!(consents[0].ip_address.as_deref(), Some("127.0.0.1"));
+    assert_eq!(
+        consents[0].user_agent.as_deref(),
+        Some("Mozilla/5.0 (Test)")
+    );
+}
+
+#[actix_rt::test]
+async fn the_configured_consent_text_version_is_recorded_by_default() {
+    let app = spawn_app().await;
+
+    create_unconfirmed_subscriber(&app).await;
+
+    let consents = consent_log(&app).await;
+    assert_eq!(consents.len(), 1);
+    assert_eq!(consents[0].source, "subscription_form");
+    assert_eq!(
+        consents[0].consent_text_version,
+        app.subscription_settings.consent_text_version
+    );
+}
+
+#[actix_rt::test]
+async fn confirming_records_a_second_consent_event() {
+    let app = spawn_app().await;
+
+    create_confirmed_subscriber(&app).await;
+
+    let consents = consent_log(&app).await;
+    assert_eq!(consents.len(), 2);
+    assert_eq!(consents[1].event, "confirmed");
+    assert_eq!(consents[1].source, "confirmation_link");
+    assert_eq!(
+        consents[1].consent_text_version,
+        consents[0].consent_text_version
+    );
+    assert_eq!(consents[1].ip_address.as_deref(), Some("127.0.0.1"));
+}
+
+#[actix_rt::test]
+async fn the_consent_log_is_part_of_the_subscriber_data() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let data: serde_json::Value = app
+        .get_subscriber_data("tdnb@hello.com")
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    let events: Vec<_> = data["consents"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| c["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(events, vec!["subscribed", "confirmed"]);
+}
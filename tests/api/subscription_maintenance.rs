@@ -0,0 +1,210 @@
+use crate::helpers::{create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app};
+use wiremock::matchers::{any, body_string_contains, method, path};
+use wiremock::{Mock, ResponseTemplate};
+use z2p::subscription_maintenance::{delete_expired_tokens, purge_unconfirmed_subscribers};
+
+#[actix_rt::test]
+async fn unconfirmed_subscribers_are_reminded_once() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET subscribed_at = now() - interval '25 hours'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.send_confirmation_reminders().await;
+    app.send_confirmation_reminders().await;
+
+    // The reminder carries a working confirmation link
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request).await;
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[actix_rt::test]
+async fn a_failed_reminder_does_not_hold_up_the_following_ones() {
+    let app = spawn_app().await;
+    {
+        let _mock_guard = Mock::given(path("/email"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount_as_scoped(&app.email_server)
+            .await;
+        for body in [
+            "name=ursula&email=ursula%40example.com",
+            "name=octavia&email=octavia%40example.com",
+        ] {
+            app.post_subscription(body.into())
+                .await
+                .error_for_status()
+                .unwrap();
+        }
+    }
+    // Ursula signed up first, her reminder is attempted first
+    sqlx::query!(
+        "UPDATE subscriptions
+        SET subscribed_at = now() - interval '26 hours'
+        WHERE email = 'ursula@example.com'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "UPDATE subscriptions
+        SET subscribed_at = now() - interval '25 hours'
+        WHERE email = 'octavia@example.com'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    Mock::given(path("/email"))
+        .and(body_string_contains("ursula@example.com"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(body_string_contains("octavia@example.com"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.send_confirmation_reminders().await;
+    // The failed reminder is not attempted again
+    app.send_confirmation_reminders().await;
+}
+
+#[actix_rt::test]
+async fn recent_and_confirmed_subscribers_are_not_reminded() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Too recent
+    app.send_confirmation_reminders().await;
+
+    // Already confirmed
+    sqlx::query!(
+        "UPDATE subscriptions
+        SET status = 'confirmed', subscribed_at = now() - interval '25 hours'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.send_confirmation_reminders().await;
+}
+
+#[actix_rt::test]
+async fn stale_unconfirmed_subscribers_are_purged_with_their_tokens() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    // Confirmed a long time ago: must be kept
+    sqlx::query!("UPDATE subscriptions SET subscribed_at = now() - interval '365 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscription(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+    sqlx::query!(
+        "UPDATE subscriptions SET subscribed_at = now() - interval '15 days'
+        WHERE status = 'pending_confirmation'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let n_purged = purge_unconfirmed_subscribers(&app.db_pool, &app.subscription_settings)
+        .await
+        .unwrap();
+
+    assert_eq!(n_purged, 1);
+    let remaining = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].email, "tdnb@hello.com");
+    let n_tokens = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM subscription_tokens t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE s.status = 'pending_confirmation'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(n_tokens, 0);
+}
+
+#[actix_rt::test]
+async fn recent_unconfirmed_subscribers_are_not_purged() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+
+    let n_purged = purge_unconfirmed_subscribers(&app.db_pool, &app.subscription_settings)
+        .await
+        .unwrap();
+
+    assert_eq!(n_purged, 0);
+}
+
+#[actix_rt::test]
+async fn long_expired_tokens_are_cleared_out() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    sqlx::query!(
+        "INSERT INTO subscription_tokens (subscription_token, subscriber_id, created_at)
+        SELECT 'long-expired-token', id, now() - interval '365 days' FROM subscriptions"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    delete_expired_tokens(&app.db_pool, &app.subscription_settings)
+        .await
+        .unwrap();
+
+    let remaining = sqlx::query!("SELECT subscription_token FROM subscription_tokens")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_ne!(remaining[0].subscription_token, "long-expired-token");
+}
@@ -0,0 +1,327 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+use chrono::Duration;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+use z2p::configuration::get_configuration;
+use z2p::preference_links::PreferenceLinks;
+
+async fn subscriber_id(app: &TestApp) -> uuid::Uuid {
+    sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+}
+
+fn token_from_url(url: &str) -> String {
+    url.split_once("token=").unwrap().1.to_owned()
+}
+
+async fn preferences_token(app: &TestApp) -> String {
+    token_from_url(&app.preference_links.url_for(subscriber_id(app).await))
+}
+
+async fn get_preferences(app: &TestApp, token: &str) -> reqwest::Response {
+    reqwest::get(format!(
+        "{}/subscriptions/preferences?token={}",
+        app.address, token
+    ))
+    .await
+    .unwrap()
+}
+
+async fn post_preferences(app: &TestApp, action: &str, form: &[(&str, &str)]) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!(
+            "{}/subscriptions/preferences/{}",
+            app.address, action
+        ))
+        .form(form)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn subscriber(app: &TestApp) -> (String, String, String) {
+    let r = sqlx::query!("SELECT name, email, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    (r.name, r.email, r.status)
+}
+
+async fn consent_events(app: &TestApp) -> Vec<(String, String)> {
+    sqlx::query!("SELECT event, source FROM subscription_consents ORDER BY recorded_at")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|c| (c.event, c.source))
+        .collect()
+}
+
+#[actix_rt::test]
+async fn newsletters_link_to_the_preference_page() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletter(serde_json::json!({
+        "title": "Newsletter #1",
+        "content": { "text": "Sample newsletter", "html": "<p>Sample newsletter</p>" }
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let received_requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value =
+        serde_json::from_slice(&received_requests.last().unwrap().body).unwrap();
+    let text = body["TextBody"].as_str().unwrap();
+    let url = text.split("Manage your subscription: ").nth(1).unwrap();
+    assert!(body["HtmlBody"].as_str().unwrap().contains(url));
+
+    let response = get_preferences(&app, &token_from_url(url)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let page = response.text().await.unwrap();
+    assert!(page.contains("tdnb@hello.com"));
+    assert!(page.contains("value=\"bn\""));
+}
+
+#[actix_rt::test]
+async fn invalid_or_expired_preference_links_are_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preferences_token(&app).await;
+
+    let tampered = format!("{}{}", &token[..token.len() - 2], "00");
+    assert_eq!(
+        get_preferences(&app, &tampered).await.status().as_u16(),
+        401
+    );
+    assert_eq!(
+        get_preferences(&app, "garbage").await.status().as_u16(),
+        401
+    );
+
+    let expired_links = PreferenceLinks::new(
+        app.base_url.clone(),
+        get_configuration().unwrap().application.hmac_secret,
+        Duration::hours(-1),
+    );
+    let expired = token_from_url(&expired_links.url_for(subscriber_id(&app).await));
+    assert_eq!(get_preferences(&app, &expired).await.status().as_u16(), 410);
+}
+
+#[actix_rt::test]
+async fn subscribers_can_update_their_name() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preferences_token(&app).await;
+
+    let response = post_preferences(&app, "name", &[("token", &token), ("name", "Ursula")]).await;
+
+    // The redirect back to the preference page is followed
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("value=\"Ursula\""));
+    assert_eq!(subscriber(&app).await.0, "Ursula");
+
+    let response = post_preferences(&app, "name", &[("token", &token), ("name", "<b>")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(subscriber(&app).await.0, "Ursula");
+}
+
+#[actix_rt::test]
+async fn paused_subscribers_do_not_receive_newsletters_until_they_resume() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preferences_token(&app).await;
+
+    let response = post_preferences(&app, "pause", &[("token", &token)]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber(&app).await.2, "paused");
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletter(serde_json::json!({
+        "title": "Newsletter #1",
+        "content": { "text": "Sample newsletter", "html": "<p>Sample newsletter</p>" }
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let response = post_preferences(&app, "resume", &[("token", &token)]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber(&app).await.2, "confirmed");
+}
+
+#[actix_rt::test]
+async fn subscribers_can_unsubscribe_from_the_preference_page() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preferences_token(&app).await;
+
+    let response = post_preferences(&app, "unsubscribe", &[("token", &token)]).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber(&app).await.2, "unsubscribed");
+}
+
+#[actix_rt::test]
+async fn email_changes_take_effect_once_the_new_address_is_verified() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preferences_token(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = post_preferences(
+        &app,
+        "email",
+        &[("token", &token), ("email", "ursula_le_guin@gmail.com")],
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Check ursula_le_guin@gmail.com"));
+    // Nothing changes until the new address is verified
+    assert_eq!(subscriber(&app).await.1, "tdnb@hello.com");
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "ursula_le_guin@gmail.com");
+    let link = app.get_confirmation_links(&email_request).await.html;
+    let response = reqwest::get(link.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber(&app).await.1, "ursula_le_guin@gmail.com");
+
+    // The link can only be used once
+    let response = reqwest::get(link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_rt::test]
+async fn email_changes_are_rejected_for_invalid_addresses() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preferences_token(&app).await;
+
+    let response = post_preferences(
+        &app,
+        "email",
+        &[("token", &token), ("email", "not-an-email")],
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_rt::test]
+async fn email_changes_to_a_suppressed_address_are_not_mailed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preferences_token(&app).await;
+    app.post_suppression(serde_json::json!({"email": "ursula_le_guin@gmail.com"}))
+        .await
+        .error_for_status()
+        .unwrap();
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = post_preferences(
+        &app,
+        "email",
+        &[("token", &token), ("email", "ursula_le_guin@gmail.com")],
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber(&app).await.1, "tdnb@hello.com");
+}
+
+#[actix_rt::test]
+async fn subscribing_again_from_the_preference_page_records_consent() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preferences_token(&app).await;
+    let n_consents = consent_events(&app).await.len();
+
+    // Resuming after a pause is not a new consent
+    post_preferences(&app, "pause", &[("token", &token)]).await;
+    post_preferences(&app, "resume", &[("token", &token)]).await;
+    assert_eq!(consent_events(&app).await.len(), n_consents);
+
+    post_preferences(&app, "unsubscribe", &[("token", &token)]).await;
+    let response = post_preferences(&app, "resume", &[("token", &token)]).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber(&app).await.2, "confirmed");
+    let consents = consent_events(&app).await;
+    assert_eq!(consents.len(), n_consents + 1);
+    assert_eq!(
+        consents.last().unwrap(),
+        &("confirmed".to_owned(), "preference_page".to_owned())
+    );
+}
+
+#[actix_rt::test]
+async fn subscribers_who_are_not_mailed_are_not_offered_delivery_actions() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = preferences_token(&app).await;
+    let test_cases = [
+        ("pending_confirmation", "not confirmed yet"),
+        ("bounced", "emails to this address bounced"),
+        ("complained", "reported as spam"),
+    ];
+
+    for (status, explanation) in test_cases {
+        sqlx::query!("UPDATE subscriptions SET status = $1", status)
+            .execute(&app.db_pool)
+            .await
+            .unwrap();
+
+        let response = get_preferences(&app, &token).await;
+
+        assert_eq!(response.status().as_u16(), 200, "{}", status);
+        let page = response.text().await.unwrap();
+        assert!(page.contains(explanation), "{}", status);
+        assert!(
+            !page.contains("You are receiving new issues."),
+            "{}",
+            status
+        );
+        for action in ["pause", "resume", "unsubscribe"] {
+            assert!(
+                !page.contains(&format!("/subscriptions/preferences/{}", action)),
+                "{} page offers to {}",
+                status,
+                action
+            );
+        }
+    }
+}
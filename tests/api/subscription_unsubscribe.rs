@@ -0,0 +1,214 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Publish an issue to the confirmed subscriber and return the body of the email they received.
+async fn deliver_newsletter(app: &TestApp) -> serde_json::Value {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletter(serde_json::json!({
+            "title": "Newsletter #1",
+            "content": {
+                "text": "Sample newsletter",
+                "html": "<p>Sample newsletter</p>"
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+
+    let received_requests = app.email_server.received_requests().await.unwrap();
+    serde_json::from_slice(&received_requests.last().unwrap().body).unwrap()
+}
+
+async fn unsubscribe_url(app: &TestApp) -> String {
+    let unsubscribe_token = sqlx::query!("SELECT unsubscribe_token FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .unsubscribe_token;
+    format!(
+        "{}/subscriptions/unsubscribe?unsubscribe_token={}",
+        app.address, unsubscribe_token
+    )
+}
+
+async fn subscription_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status
+}
+
+#[actix_rt::test]
+async fn newsletters_carry_one_click_unsubscribe_headers() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let email = deliver_newsletter(&app).await;
+
+    let unsubscribe_token = sqlx::query!("SELECT unsubscribe_token FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .unsubscribe_token;
+    assert_eq!(
+        email["Headers"],
+        serde_json::json!([
+            {
+                "Name": "List-Unsubscribe",
+                "Value": format!(
+                    "<{}/subscriptions/unsubscribe?unsubscribe_token={}>",
+                    app.base_url, unsubscribe_token
+                )
+            },
+            {
+                "Name": "List-Unsubscribe-Post",
+                "Value": "List-Unsubscribe=One-Click"
+            }
+        ])
+    );
+}
+
+#[actix_rt::test]
+async fn the_unsubscribe_link_asks_for_confirmation_before_unsubscribing() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let url = unsubscribe_url(&app).await;
+
+    // Following the link, e.g. when a mail scanner prefetches it, changes nothing
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let page = response.text().await.unwrap();
+    let form_action = url.trim_start_matches(&app.address);
+    assert!(page.contains(&format!(r#"<form action="{}" method="post">"#, form_action)));
+    assert_eq!(subscription_status(&app).await, "confirmed");
+
+    // Submitting the form unsubscribes
+    let response = reqwest::Client::new().post(&url).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscription_status(&app).await, "unsubscribed");
+}
+
+#[actix_rt::test]
+async fn the_unsubscribe_form_escapes_the_token() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    sqlx::query!(r#"UPDATE subscriptions SET unsubscribe_token = '"><script>'"#)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/subscriptions/unsubscribe", app.address))
+        .query(&[("unsubscribe_token", r#""><script>"#)])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let page = response.text().await.unwrap();
+    assert!(!page.contains("<script>"));
+    assert!(page.contains("unsubscribe_token=&quot;&gt;&lt;script&gt;"));
+}
+
+#[actix_rt::test]
+async fn one_click_unsubscribe_requests_unsubscribe_the_subscriber() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let response = reqwest::Client::new()
+        .post(unsubscribe_url(&app).await)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscription_status(&app).await, "unsubscribed");
+}
+
+#[actix_rt::test]
+async fn unsubscribed_subscribers_do_not_receive_newsletters() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    reqwest::Client::new()
+        .post(unsubscribe_url(&app).await)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletter(serde_json::json!({
+            "title": "Newsletter #2",
+            "content": {
+                "text": "Sample newsletter",
+                "html": "<p>Sample newsletter</p>"
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_rt::test]
+async fn unsubscribing_with_an_unknown_token_is_rejected() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!(
+        "{}/subscriptions/unsubscribe?unsubscribe_token=not-a-real-token",
+        app.address
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/subscriptions/unsubscribe?unsubscribe_token=not-a-real-token",
+            app.address
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = reqwest::get(format!("{}/subscriptions/unsubscribe", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_rt::test]
+async fn unsubscribing_keeps_the_status_of_subscribers_who_are_not_mailed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let url = unsubscribe_url(&app).await;
+
+    for status in ["pending_confirmation", "bounced", "complained"] {
+        sqlx::query!("UPDATE subscriptions SET status = $1", status)
+            .execute(&app.db_pool)
+            .await
+            .unwrap();
+
+        let response = reqwest::Client::new().post(&url).send().await.unwrap();
+
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(subscription_status(&app).await, status);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{create_confirmed_subscriber, spawn_app};
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -89,6 +89,159 @@ async fn subscribe_returns_400_when_data_is_empty() {
     }
 }
 
+#[actix_rt::test]
+async fn subscribe_accepts_json_bodies() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscription_json(serde_json::json!({"name": "bn", "email": "tdnb@hello.com"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, "tdnb@hello.com");
+    assert_eq!(saved.name, "bn");
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[actix_rt::test]
+async fn subscribe_lists_every_invalid_field() {
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({"name": "", "email": "not-an-email"}),
+            vec!["email", "name"],
+        ),
+        (serde_json::json!({"name": "bn"}), vec!["email"]),
+        (
+            serde_json::json!({"email": "tdnb@hello.com", "name": "(bn)"}),
+            vec!["name"],
+        ),
+    ];
+
+    for (body, invalid_fields) in test_cases {
+        let response = app.post_subscription_json(body.clone()).await;
+
+        assert_eq!(response.status().as_u16(), 400, "Payload: {}", body);
+        let errors: serde_json::Value = response.json().await.unwrap();
+        let fields: Vec<_> = errors["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, invalid_fields, "Payload: {}", body);
+    }
+}
+
+#[actix_rt::test]
+async fn validation_errors_say_why_a_field_was_rejected() {
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({"name": " ", "email": "tdnb@hello.com"}),
+            "name",
+            "empty",
+        ),
+        (
+            serde_json::json!({"name": "a".repeat(257), "email": "tdnb@hello.com"}),
+            "name",
+            "too_long",
+        ),
+        (
+            serde_json::json!({"name": "<bn>", "email": "tdnb@hello.com"}),
+            "name",
+            "forbidden_characters",
+        ),
+        (
+            serde_json::json!({"name": "bn", "email": "tdnb.hello.com"}),
+            "email",
+            "invalid_format",
+        ),
+    ];
+
+    for (body, field, code) in test_cases {
+        let response = app.post_subscription_json(body).await;
+
+        assert_eq!(response.status().as_u16(), 400);
+        let errors: serde_json::Value = response.json().await.unwrap();
+        let error = &errors["errors"][0];
+        assert_eq!(error["field"], field);
+        assert_eq!(error["code"], code);
+        assert!(error["message"]
+            .as_str()
+            .unwrap()
+            .starts_with(&format!("The {}", field)));
+    }
+}
+
+#[actix_rt::test]
+async fn too_long_errors_include_the_maximum_length() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscription_json(
+            serde_json::json!({"name": "a".repeat(257), "email": "tdnb@hello.com"}),
+        )
+        .await;
+
+    let errors: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(errors["errors"][0]["max"], 256);
+}
+
+#[actix_rt::test]
+async fn form_validation_errors_are_also_structured() {
+    let app = spawn_app().await;
+
+    let response = app.post_subscription("name=bn&email=".into()).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let errors: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(errors["errors"][0]["field"], "email");
+    assert!(errors["errors"][0]["message"].is_string());
+}
+
+#[actix_rt::test]
+async fn subscribe_returns_400_for_malformed_json() {
+    let app = spawn_app().await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/json")
+        .body("{\"name\": ")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    let errors: serde_json::Value = response.json().await.unwrap();
+    assert!(errors["errors"][0]["message"].is_string());
+}
+
+#[actix_rt::test]
+async fn subscribe_returns_415_for_other_content_types() {
+    let app = spawn_app().await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "text/plain")
+        .body("name=bn&email=tdnb%40hello.com")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 415);
+}
+
 #[actix_rt::test]
 async fn subscribe_sends_a_confirmation_email_for_valid_data() {
     // Arrange
@@ -131,3 +284,144 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     // Assert
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[actix_rt::test]
+async fn subscribing_twice_while_pending_resends_a_fresh_confirmation_email() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=bn&email=tdnb%40hello.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let first_response = app.post_subscription(body.into()).await;
+    let second_response = app.post_subscription(body.into()).await;
+
+    // Assert
+    assert_eq!(first_response.status().as_u16(), 200);
+    assert_eq!(second_response.status().as_u16(), 200);
+
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let first_link = app.get_confirmation_links(&email_requests[0]).await.html;
+    let second_link = app.get_confirmation_links(&email_requests[1]).await.html;
+    assert_ne!(first_link, second_link);
+
+    // Only the latest link is still valid
+    let response = reqwest::get(first_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let response = reqwest::get(second_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[actix_rt::test]
+async fn subscribing_with_a_confirmed_email_does_not_reveal_it_is_on_the_list() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let body = "name=bn&email=tdnb%40hello.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscription(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[actix_rt::test]
+async fn unsubscribed_subscribers_can_subscribe_again() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let body = "name=bn&email=tdnb%40hello.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscription(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request).await;
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[actix_rt::test]
+async fn subscribing_does_not_reset_subscribers_who_are_not_mailed() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let body = "name=bn&email=tdnb%40hello.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    for status in ["bounced", "complained"] {
+        sqlx::query!("UPDATE subscriptions SET status = $1", status)
+            .execute(&app.db_pool)
+            .await
+            .unwrap();
+
+        // Act
+        let response = app.post_subscription(body.into()).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 200, "{}", status);
+        let saved = sqlx::query!("SELECT status FROM subscriptions")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+        assert_eq!(saved.status, status);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn suppressions(app: &TestApp) -> Vec<serde_json::Value> {
+    let response = app.get_suppressions().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let suppressions: serde_json::Value = response.json().await.unwrap();
+    suppressions.as_array().unwrap().clone()
+}
+
+#[actix_rt::test]
+async fn admins_can_add_list_and_remove_suppressed_emails() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_suppression(serde_json::json!({"email": "ursula_le_guin@gmail.com"}))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app
+        .post_suppression(serde_json::json!({"email": "octavia@butler.com", "reason": "complaint"}))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let entries = suppressions(&app).await;
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["email"], "ursula_le_guin@gmail.com");
+    assert_eq!(entries[0]["reason"], "manual");
+    assert_eq!(entries[1]["reason"], "complaint");
+
+    let response = app
+        .post_remove_suppression("Ursula_Le_Guin@gmail.com")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let entries = suppressions(&app).await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["email"], "octavia@butler.com");
+}
+
+#[actix_rt::test]
+async fn invalid_suppressions_are_rejected_with_400() {
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({"email": "not-an-email"}),
+            "invalid email",
+        ),
+        (
+            serde_json::json!({"email": "ursula_le_guin@gmail.com", "reason": "erased"}),
+            "erased reason",
+        ),
+    ];
+
+    for (body, description) in test_cases {
+        let response = app.post_suppression(body).await;
+        assert_eq!(response.status().as_u16(), 400, "{}", description);
+    }
+    assert!(suppressions(&app).await.is_empty());
+}
+
+#[actix_rt::test]
+async fn removing_an_address_that_is_not_suppressed_returns_404() {
+    let app = spawn_app().await;
+
+    let response = app.post_remove_suppression("nobody@hello.com").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn the_suppression_list_requires_admin_credentials() {
+    let app = spawn_app().await;
+
+    let list = reqwest::Client::new()
+        .get(format!("{}/admin/suppressions", &app.address))
+        .send()
+        .await
+        .unwrap();
+    let add = reqwest::Client::new()
+        .post(format!("{}/admin/suppressions", &app.address))
+        .json(&serde_json::json!({"email": "ursula_le_guin@gmail.com"}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(list.status().as_u16(), 401);
+    assert_eq!(add.status().as_u16(), 401);
+}
+
+#[actix_rt::test]
+async fn suppressed_addresses_are_not_sent_a_confirmation_email() {
+    let app = spawn_app().await;
+    app.post_suppression(serde_json::json!({"email": "tdnb@hello.com"}))
+        .await
+        .error_for_status()
+        .unwrap();
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscription("name=bn&email=tdnb%40hello.com".into())
+        .await;
+
+    // The caller must not be able to tell the address is suppressed
+    assert_eq!(response.status().as_u16(), 200);
+    let n_subscribers = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_subscribers, 0);
+}
+
+#[actix_rt::test]
+async fn suppressed_subscribers_are_skipped_by_newsletter_delivery() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_suppression(serde_json::json!({"email": "tdnb@hello.com"}))
+        .await
+        .error_for_status()
+        .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletter(serde_json::json!({
+        "title": "Newsletter #1",
+        "content": {
+            "text": "Sample newsletter",
+            "html": "<p>Sample newsletter</p>"
+        }
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    let outcome = sqlx::query!("SELECT outcome FROM issue_delivery_log")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .outcome;
+    assert_eq!(outcome, "skipped");
+}
+
+#[actix_rt::test]
+async fn bounces_and_complaints_are_added_to_the_suppression_list() {
+    let app = spawn_app().await;
+
+    app.post_postmark_webhook(include_str!("fixtures/postmark_hard_bounce.json"))
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let entries = suppressions(&app).await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["email"], "tdnb@hello.com");
+    assert_eq!(entries[0]["reason"], "bounce");
+
+    // The first reason is kept
+    app.post_postmark_webhook(include_str!("fixtures/postmark_spam_complaint.json"))
+        .await
+        .error_for_status()
+        .unwrap();
+    assert_eq!(suppressions(&app).await[0]["reason"], "bounce");
+}
+
+#[actix_rt::test]
+async fn erased_subscribers_are_suppressed_without_keeping_their_email() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_suppression(serde_json::json!({"email": "tdnb@hello.com"}))
+        .await
+        .error_for_status()
+        .unwrap();
+
+    app.post_erase_subscriber("tdnb@hello.com")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // The manual suppression outlives the erasure
+    let entries = suppressions(&app).await;
+    assert_eq!(entries.len(), 1);
+    assert!(entries[0]["email"].is_null());
+    assert_eq!(entries[0]["reason"], "manual");
+}
+
+#[actix_rt::test]
+async fn erased_subscribers_can_sign_up_again() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_erase_subscriber("tdnb@hello.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscription("name=bn&email=tdnb%40hello.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Signing up is not enough, anyone can fill in the form
+    let entries = suppressions(&app).await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["reason"], "erased");
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request).await;
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    assert!(suppressions(&app).await.is_empty());
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[actix_rt::test]
+async fn bounced_addresses_stay_suppressed_after_an_erasure() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_postmark_webhook(include_str!("fixtures/postmark_hard_bounce.json"))
+        .await
+        .error_for_status()
+        .unwrap();
+    app.post_erase_subscriber("tdnb@hello.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscription("name=bn&email=tdnb%40hello.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let entries = suppressions(&app).await;
+    assert_eq!(entries.len(), 1);
+    assert!(entries[0]["email"].is_null());
+    assert_eq!(entries[0]["reason"], "bounce");
+}
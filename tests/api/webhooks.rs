@@ -0,0 +1,136 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+// Example payloads from the Postmark webhook documentation, addressed to our test subscriber
+const HARD_BOUNCE: &str = include_str!("fixtures/postmark_hard_bounce.json");
+const SOFT_BOUNCE: &str = include_str!("fixtures/postmark_soft_bounce.json");
+const SPAM_COMPLAINT: &str = include_str!("fixtures/postmark_spam_complaint.json");
+const DELIVERY: &str = include_str!("fixtures/postmark_delivery.json");
+
+async fn subscriber_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status
+}
+
+#[actix_rt::test]
+async fn hard_bounces_mark_the_subscriber_as_bounced() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let response = app.post_postmark_webhook(HARD_BOUNCE).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app).await, "bounced");
+}
+
+#[actix_rt::test]
+async fn soft_bounces_are_ignored() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let response = app.post_postmark_webhook(SOFT_BOUNCE).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+}
+
+#[actix_rt::test]
+async fn spam_complaints_mark_the_subscriber_as_complained() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let response = app.post_postmark_webhook(SPAM_COMPLAINT).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app).await, "complained");
+}
+
+#[actix_rt::test]
+async fn a_later_bounce_does_not_overwrite_a_complaint() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    app.post_postmark_webhook(SPAM_COMPLAINT).await;
+    app.post_postmark_webhook(HARD_BOUNCE).await;
+
+    assert_eq!(subscriber_status(&app).await, "complained");
+}
+
+#[actix_rt::test]
+async fn other_record_types_are_acknowledged_and_ignored() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let response = app.post_postmark_webhook(DELIVERY).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+}
+
+#[actix_rt::test]
+async fn events_for_unknown_addresses_are_acknowledged() {
+    let app = spawn_app().await;
+
+    let response = app.post_postmark_webhook(HARD_BOUNCE).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_rt::test]
+async fn webhooks_without_valid_credentials_are_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    let missing = reqwest::Client::new()
+        .post(format!("{}/webhooks/postmark", &app.address))
+        .header("Content-Type", "application/json")
+        .body(HARD_BOUNCE)
+        .send()
+        .await
+        .unwrap();
+    let wrong = reqwest::Client::new()
+        .post(format!("{}/webhooks/postmark", &app.address))
+        .basic_auth(&app.webhook_settings.username, Some("not-the-secret"))
+        .header("Content-Type", "application/json")
+        .body(HARD_BOUNCE)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(missing.status().as_u16(), 401);
+    assert_eq!(
+        r#"Basic realm="webhooks""#,
+        missing.headers()["WWW-Authenticate"]
+    );
+    assert_eq!(wrong.status().as_u16(), 401);
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+}
+
+#[actix_rt::test]
+async fn bounced_subscribers_do_not_receive_newsletters() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_postmark_webhook(HARD_BOUNCE).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletter(serde_json::json!({
+            "title": "Newsletter #1",
+            "content": {
+                "text": "Sample newsletter",
+                "html": "<p>Sample newsletter</p>"
+            }
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+}